@@ -10,8 +10,34 @@ pub struct CanvasImage {
     height: u32,
 }
 
+/// One of the four channels stored in a `CanvasImage`
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Channel {
+    Red,
+    Green,
+    Blue,
+    Alpha,
+}
+
+impl Channel {
+    /// The channels that carry color, i.e. everything except alpha
+    pub const COLOR: [Channel; 3] = [Channel::Red, Channel::Green, Channel::Blue];
+
+    /// Where the channel sits inside an RGBA pixel
+    pub fn offset(&self) -> usize {
+        match self {
+            Channel::Red => 0,
+            Channel::Green => 1,
+            Channel::Blue => 2,
+            Channel::Alpha => 3,
+        }
+    }
+}
+
 mod iterator;
 
+use crate::float_image::FloatImage;
 use crate::image_index;
 use crate::image_index::{CircularIndexedImage, ReflectiveIndexedImage, ZeroPaddedImage};
 pub use iterator::*;
@@ -122,6 +148,99 @@ impl CanvasImage {
         }
     }
 
+    /**************************** channel planes ***********************************/
+
+    /// Copy a channel out into a `FloatImage`, with the intensities scaled to [0, 1]
+    pub fn plane(&self, channel: Channel) -> FloatImage {
+        let offset = channel.offset();
+        let data = self
+            .data
+            .chunks_exact(4)
+            .map(|pixel| pixel[offset] as f64 / 255.0)
+            .collect();
+
+        FloatImage::from_vec_with_size(data, self.width, self.height)
+    }
+
+    /// Overwrite a channel with the intensities of `plane`, which are clamped to [0, 1]
+    pub fn set_plane(&mut self, channel: Channel, plane: &FloatImage) {
+        assert_eq!(plane.horizontal_size(), self.width);
+        assert_eq!(plane.vertical_size(), self.height);
+
+        let offset = channel.offset();
+        Iterator::zip(self.data.chunks_exact_mut(4), plane.data().iter()).for_each(|(pixel, v)| {
+            pixel[offset] = (v * 255.0).round().clamp(0.0, 255.0) as u8;
+        });
+    }
+
+    /// The luminance of every pixel, sRGB encoded so it looks right when displayed as greyscale
+    ///
+    /// sRGB -> Linear RGB -> Luminance -> sRGB, with everything in [0, 1]
+    pub fn luminance(&self) -> FloatImage {
+        self.greyscale_plane(GreyscaleMethod::Luminance)
+    }
+
+    /// Split the color of every pixel into the three components of `space`, in the units of
+    /// the matching struct in `color_space` (e.g. L in [0, 100] for Lab, hue in degrees)
+    pub fn to_planes(&self, space: ColorSpace) -> [FloatImage; 3] {
+        let mut planes = [(); 3].map(|_| Vec::with_capacity(self.data.len() / 4));
+        for (r, g, b, _) in self.rgba_iter() {
+            let components = space.from_srgb(Srgb::from_u8(r, g, b));
+            for (plane, component) in planes.iter_mut().zip(components) {
+                plane.push(component);
+            }
+        }
+
+        planes.map(|plane| FloatImage::from_vec_with_size(plane, self.width, self.height))
+    }
+
+    /// Build an opaque image out of the three components of `space`, out of gamut colors are
+    /// clamped
+    pub fn from_planes(planes: &[FloatImage; 3], space: ColorSpace) -> CanvasImage {
+        let (width, height) = (planes[0].horizontal_size(), planes[0].vertical_size());
+        let mut image = CanvasImage::from_vec_with_size(
+            vec![255; (width * height * 4) as usize],
+            width,
+            height,
+        );
+        image.set_planes(planes, space);
+
+        image
+    }
+
+    /// Overwrite the color of every pixel with the three components of `space`, leaving alpha
+    /// untouched
+    pub fn set_planes(&mut self, planes: &[FloatImage; 3], space: ColorSpace) {
+        for plane in planes {
+            assert_eq!(plane.horizontal_size(), self.width);
+            assert_eq!(plane.vertical_size(), self.height);
+        }
+
+        let [p, q, r] = planes.each_ref().map(|plane| plane.data());
+        for (i, pixel) in self.data.chunks_exact_mut(4).enumerate() {
+            let (r, g, b) = space.to_srgb([p[i], q[i], r[i]]).to_u8();
+            pixel[..3].copy_from_slice(&[r, g, b]);
+        }
+    }
+
+    /// Treat the image as binary, a pixel is foreground (white) when its luminance is at least
+    /// one half
+    pub fn foreground(&self) -> Vec<bool> {
+        self.luminance().data().iter().map(|v| *v >= 0.5).collect()
+    }
+
+    /// Build an opaque binary image, foreground pixels are white and the rest are black
+    pub fn from_foreground(mask: Vec<bool>, width: u32, height: u32) -> CanvasImage {
+        assert_eq!(mask.len(), (width * height) as usize);
+
+        let rgba = mask.into_iter().flat_map(|foreground| {
+            let v = if foreground { 255 } else { 0 };
+            [v, v, v, 255]
+        });
+
+        CanvasImage::from_vec_with_size(rgba.collect(), width, height)
+    }
+
     /***************************** histograms *********************************/
     pub fn blue_histogram(&self) -> Histogram {
         let mut b_channel = self.b_iter();
@@ -179,22 +298,31 @@ impl CanvasImage {
     /// sRGB -> Linear RGB -> Luminance -> sRGB
     /// alpha is left untouched
     pub fn convert_to_greyscale(&mut self) {
+        self.convert_to_greyscale_with(GreyscaleMethod::Luminance);
+    }
+
+    /// convert an color image to a greyscale image, every pixel gets its grey value under
+    /// `method` in all three color channels
+    /// alpha is left untouched
+    pub fn convert_to_greyscale_with(&mut self, method: GreyscaleMethod) {
         self.rgba_iter_mut().for_each(|(r, g, b, _)| {
-            let mut linear_r = *r as f64;
-            let mut linear_g = *g as f64;
-            let mut linear_b = *b as f64;
+            let grey = method.grey(Srgb::from_u8(*r, *g, *b));
+            let grey = (grey * 255.0).round() as u8;
 
-            linear_r = linear_r.linearize();
-            linear_g = linear_g.linearize();
-            linear_b = linear_b.linearize();
+            *r = grey;
+            *g = grey;
+            *b = grey;
+        });
+    }
 
-            let mut lumma = to_luminance(linear_r, linear_g, linear_b);
-            lumma = to_srgb(lumma);
+    /// The grey value of every pixel under `method` as a single channel, in [0, 1]
+    pub fn greyscale_plane(&self, method: GreyscaleMethod) -> FloatImage {
+        let data = self
+            .rgba_iter()
+            .map(|(r, g, b, _)| method.grey(Srgb::from_u8(r, g, b)))
+            .collect();
 
-            *r = lumma.clamp(u8::MIN as f64, u8::MAX as f64) as u8;
-            *g = lumma.clamp(u8::MIN as f64, u8::MAX as f64) as u8;
-            *b = lumma.clamp(u8::MIN as f64, u8::MAX as f64) as u8;
-        });
+        FloatImage::from_vec_with_size(data, self.width, self.height)
     }
 
     /// Remove all pixels around the edges of the image that are transparent
@@ -341,13 +469,51 @@ impl ReflectiveIndexedImage for CanvasImage {
     }
 }
 
+mod adjustments;
+mod channels;
+mod clahe;
+mod components;
+mod contours;
 mod crop;
+mod denoise;
+mod distance_transform;
 mod edge_detection;
+mod features;
 mod filters;
-
-use crate::color_space::{to_luminance, to_srgb, Linearize};
+mod hough;
+mod matching;
+mod morphology;
+mod orb;
+mod selection;
+mod sharpen;
+mod smoothing;
+#[cfg(test)]
+mod test_util;
+mod tone;
+mod voronoi;
+mod white_balance;
+
+use crate::color_space::{ColorSpace, GreyscaleMethod, Srgb};
+pub use adjustments::*;
+pub use channels::*;
+pub use clahe::*;
+pub use components::*;
+pub use contours::*;
+pub use denoise::*;
+pub use distance_transform::*;
 pub use edge_detection::*;
+pub use features::*;
 pub use filters::*;
+pub use hough::*;
+pub use matching::*;
+pub use morphology::*;
+pub use orb::*;
+pub use selection::*;
+pub use sharpen::*;
+pub use smoothing::*;
+pub use tone::*;
+pub use voronoi::*;
+pub use white_balance::*;
 
 #[wasm_bindgen]
 pub fn greyscale(image: ImageData) -> ImageData {
@@ -355,3 +521,23 @@ pub fn greyscale(image: ImageData) -> ImageData {
     canvas_image.convert_to_greyscale();
     canvas_image.into()
 }
+
+#[wasm_bindgen]
+pub fn greyscale_with(image: ImageData, method: GreyscaleMethod) -> ImageData {
+    let mut canvas_image = CanvasImage::from_image_data(image);
+    canvas_image.convert_to_greyscale_with(method);
+    canvas_image.into()
+}
+
+/// # Returns
+///  one byte per pixel, row by row, the grey value under `method`
+#[wasm_bindgen]
+pub fn greyscale_channel(image: ImageData, method: GreyscaleMethod) -> Vec<u8> {
+    let canvas_image = CanvasImage::from_image_data(image);
+    canvas_image
+        .greyscale_plane(method)
+        .data()
+        .iter()
+        .map(|v| (v * 255.0).round() as u8)
+        .collect()
+}
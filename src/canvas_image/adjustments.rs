@@ -0,0 +1,215 @@
+use super::*;
+use crate::color_space::{Hsl, Lch};
+
+/// LCh hue of typical skin tones, in degrees, and how far around it vibrance holds back
+const SKIN_HUE: f64 = 55.0;
+const SKIN_HUE_SPREAD: f64 = 25.0;
+/// About the largest LCh chroma inside sRGB, vibrance boosts colors less the closer they are
+const MAX_CHROMA: f64 = 130.0;
+
+/// A band of hues, as in the hue/saturation dialogs of image editors
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HueRange {
+    Reds,
+    Yellows,
+    Greens,
+    Cyans,
+    Blues,
+    Magentas,
+}
+
+impl HueRange {
+    /// How much a color of `hue` (HSL, in degrees) belongs to the range: fully within 15° of
+    /// the center, fading out linearly until 45° away so neighbouring ranges overlap smoothly
+    fn weight(&self, hue: f64) -> f64 {
+        let center = 60.0 * *self as u8 as f64;
+        let distance = (hue - center).rem_euclid(360.0);
+        let distance = distance.min(360.0 - distance);
+
+        ((45.0 - distance) / 30.0).clamp(0.0, 1.0)
+    }
+}
+
+/// Shift the hue by `hue_shift` degrees, scale the saturation and move the lightness towards
+/// black or white, with `saturation` and `lightness` in [-1, 1]
+fn adjust(hsl: Hsl, hue_shift: f64, saturation: f64, lightness: f64) -> Hsl {
+    let l = if lightness < 0.0 {
+        hsl.l * (1.0 + lightness)
+    } else {
+        hsl.l + (1.0 - hsl.l) * lightness
+    };
+
+    Hsl {
+        h: (hsl.h + hue_shift).rem_euclid(360.0),
+        s: (hsl.s * (1.0 + saturation)).clamp(0.0, 1.0),
+        l: l.clamp(0.0, 1.0),
+    }
+}
+
+impl CanvasImage {
+    /// Replace the color of every pixel with `f` of it, alpha is left untouched
+    pub fn map_colors(&self, f: impl Fn(Srgb) -> Srgb) -> CanvasImage {
+        let rgba = self
+            .rgba_iter()
+            .flat_map(|(r, g, b, a)| {
+                let (r, g, b) = f(Srgb::from_u8(r, g, b)).to_u8();
+                [r, g, b, a]
+            })
+            .collect();
+
+        CanvasImage::from_vec_with_size(rgba, self.width, self.height)
+    }
+
+    /// Hue/saturation/lightness adjustment
+    ///
+    /// # Arguments
+    /// * hue_shift: degrees to rotate every hue by
+    /// * saturation: -1 removes all color, 0 keeps it and 1 doubles it
+    /// * lightness: -1 turns everything black, 0 keeps it and 1 turns everything white
+    pub fn adjust_hsl(&self, hue_shift: f64, saturation: f64, lightness: f64) -> CanvasImage {
+        self.map_colors(|color| adjust(color.into(), hue_shift, saturation, lightness).into())
+    }
+
+    /// Like `adjust_hsl` but only for the colors whose hue falls in `range`, colors near the
+    /// edge of the range are adjusted partially
+    pub fn adjust_hue_range(
+        &self,
+        range: HueRange,
+        hue_shift: f64,
+        saturation: f64,
+        lightness: f64,
+    ) -> CanvasImage {
+        self.map_colors(|color| {
+            let hsl = Hsl::from(color);
+            // greys have no hue to select them by
+            let weight = if hsl.s > 0.0 {
+                range.weight(hsl.h)
+            } else {
+                0.0
+            };
+
+            adjust(
+                hsl,
+                hue_shift * weight,
+                saturation * weight,
+                lightness * weight,
+            )
+            .into()
+        })
+    }
+
+    /// Saturation boost that goes easy on colors that are already saturated and on skin tones,
+    /// so portraits don't turn orange. Works on the chroma in LCh, negative `amount`s mute the
+    /// dull colors first.
+    pub fn vibrance(&self, amount: f64) -> CanvasImage {
+        self.map_colors(|color| {
+            let Lch { l, c, h } = color.into();
+
+            let hue_distance = (h - SKIN_HUE).rem_euclid(360.0);
+            let hue_distance = hue_distance.min(360.0 - hue_distance);
+            let skin = (-(hue_distance / SKIN_HUE_SPREAD).powi(2)).exp();
+            let headroom = (1.0 - c / MAX_CHROMA).max(0.0);
+
+            let c = c * (1.0 + amount * headroom * (1.0 - skin)).max(0.0);
+            Lch { l, c, h }.into()
+        })
+    }
+
+    /// Tint the image: every pixel gets `hue` (degrees) and `saturation` (in [0, 1]) and keeps
+    /// its own lightness
+    pub fn colorize(&self, hue: f64, saturation: f64) -> CanvasImage {
+        self.map_colors(|color| {
+            Hsl {
+                h: hue.rem_euclid(360.0),
+                s: saturation.clamp(0.0, 1.0),
+                l: Hsl::from(color).l,
+            }
+            .into()
+        })
+    }
+}
+
+#[wasm_bindgen]
+pub fn adjust_hsl(image: ImageData, hue_shift: f64, saturation: f64, lightness: f64) -> ImageData {
+    let image = CanvasImage::from_image_data(image);
+    image.adjust_hsl(hue_shift, saturation, lightness).into()
+}
+
+#[wasm_bindgen]
+pub fn adjust_hue_range(
+    image: ImageData,
+    range: HueRange,
+    hue_shift: f64,
+    saturation: f64,
+    lightness: f64,
+) -> ImageData {
+    let image = CanvasImage::from_image_data(image);
+    image
+        .adjust_hue_range(range, hue_shift, saturation, lightness)
+        .into()
+}
+
+#[wasm_bindgen]
+pub fn vibrance(image: ImageData, amount: f64) -> ImageData {
+    let image = CanvasImage::from_image_data(image);
+    image.vibrance(amount).into()
+}
+
+#[wasm_bindgen]
+pub fn colorize(image: ImageData, hue: f64, saturation: f64) -> ImageData {
+    let image = CanvasImage::from_image_data(image);
+    image.colorize(hue, saturation).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixels(colors: &[(u8, u8, u8)]) -> CanvasImage {
+        let rgba = colors.iter().flat_map(|(r, g, b)| [*r, *g, *b, 200]);
+        CanvasImage::from_vec_with_size(rgba.collect(), colors.len() as u32, 1)
+    }
+
+    #[test]
+    fn hsl_adjustments() {
+        let image = pixels(&[(255, 0, 0), (0, 0, 255), (100, 100, 100)]);
+
+        let shifted = image.adjust_hsl(120.0, 0.0, 0.0);
+        assert_eq!(shifted.rgba(0, 0), Some((0, 255, 0, 200)));
+        assert_eq!(shifted.rgba(1, 0), Some((255, 0, 0, 200)));
+
+        let grey = image.adjust_hsl(0.0, -1.0, 0.0);
+        assert_eq!(grey.rgba(0, 0), Some((128, 128, 128, 200)));
+        assert_eq!(
+            image.adjust_hsl(0.0, 0.0, 1.0).rgba(2, 0),
+            Some((255, 255, 255, 200))
+        );
+
+        // only the reds lose their color
+        let reds = image.adjust_hue_range(HueRange::Reds, 0.0, -1.0, 0.0);
+        assert_eq!(reds.rgba(0, 0), Some((128, 128, 128, 200)));
+        assert_eq!(reds.rgba(1, 0), Some((0, 0, 255, 200)));
+        assert_eq!(reds.rgba(2, 0), Some((100, 100, 100, 200)));
+
+        let sepia = image.colorize(30.0, 0.5);
+        assert_eq!(sepia.rgba(2, 0), Some((150, 100, 50, 200)));
+    }
+
+    #[test]
+    fn vibrance_spares_skin_and_saturated_colors() {
+        let chroma = |image: &CanvasImage, x| {
+            let (r, g, b, _) = image.rgba(x, 0).unwrap();
+            Lch::from(Srgb::from_u8(r, g, b)).c
+        };
+        // a dull blue, a saturated blue and a skin tone
+        let image = pixels(&[(110, 120, 150), (20, 20, 240), (225, 170, 140)]);
+        let boosted = image.vibrance(0.5);
+
+        let gain = |x| chroma(&boosted, x) / chroma(&image, x);
+        assert!(gain(0) > 1.3);
+        assert!(gain(1) < 1.1);
+        assert!(gain(2) < 1.1);
+        assert_eq!(boosted.a(0, 0), Some(200));
+    }
+}
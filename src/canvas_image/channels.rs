@@ -0,0 +1,263 @@
+use super::*;
+use color_eyre::eyre::{bail, eyre};
+
+/// Ready made color matrices for `channel_mix`
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ColorMatrixPreset {
+    /// Same as the CSS `sepia(1)` filter
+    Sepia,
+    /// Negative of the colors, alpha stays
+    Invert,
+    /// Rec.709 luma in all three channels, same as the CSS `grayscale(1)` filter
+    Grayscale,
+}
+
+impl ColorMatrixPreset {
+    #[rustfmt::skip]
+    pub fn matrix(&self) -> [f64; 20] {
+        match self {
+            ColorMatrixPreset::Sepia => [
+                0.393, 0.769, 0.189, 0.0, 0.0,
+                0.349, 0.686, 0.168, 0.0, 0.0,
+                0.272, 0.534, 0.131, 0.0, 0.0,
+                0.0,   0.0,   0.0,   1.0, 0.0,
+            ],
+            ColorMatrixPreset::Invert => [
+                -1.0,  0.0,  0.0, 0.0, 1.0,
+                 0.0, -1.0,  0.0, 0.0, 1.0,
+                 0.0,  0.0, -1.0, 0.0, 1.0,
+                 0.0,  0.0,  0.0, 1.0, 0.0,
+            ],
+            ColorMatrixPreset::Grayscale => [
+                0.2126, 0.7152, 0.0722, 0.0, 0.0,
+                0.2126, 0.7152, 0.0722, 0.0, 0.0,
+                0.2126, 0.7152, 0.0722, 0.0, 0.0,
+                0.0,    0.0,    0.0,    1.0, 0.0,
+            ],
+        }
+    }
+}
+
+/// Where one output channel of a swizzle comes from
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Source {
+    Channel(Channel),
+    Constant(u8),
+}
+
+impl Source {
+    fn parse(c: char) -> Option<Source> {
+        let source = match c.to_ascii_lowercase() {
+            'r' => Source::Channel(Channel::Red),
+            'g' => Source::Channel(Channel::Green),
+            'b' => Source::Channel(Channel::Blue),
+            'a' => Source::Channel(Channel::Alpha),
+            '0' => Source::Constant(0),
+            '1' => Source::Constant(255),
+            _ => return None,
+        };
+
+        Some(source)
+    }
+}
+
+impl CanvasImage {
+    /// A single channel as an opaque greyscale image
+    pub fn extract_channel(&self, channel: Channel) -> CanvasImage {
+        let rgba = self
+            .channel_iter(channel)
+            .flat_map(|v| [v, v, v, 255])
+            .collect();
+
+        CanvasImage::from_vec_with_size(rgba, self.width, self.height)
+    }
+
+    /// Build an image out of one image per channel, the inverse of `extract_channel`. The red
+    /// channel of each image is used, which for greyscale images is the grey value. Without
+    /// `alpha` the result is opaque.
+    pub fn merge_channels(
+        red: &CanvasImage,
+        green: &CanvasImage,
+        blue: &CanvasImage,
+        alpha: Option<&CanvasImage>,
+    ) -> CanvasImage {
+        let (width, height) = (red.width, red.height);
+        for image in [Some(green), Some(blue), alpha].into_iter().flatten() {
+            assert_eq!(image.width, width, "all channels must be the same size");
+            assert_eq!(image.height, height, "all channels must be the same size");
+        }
+
+        let alpha: Box<dyn Iterator<Item = u8>> = match alpha {
+            Some(alpha) => Box::new(alpha.r_iter()),
+            None => Box::new(std::iter::repeat(255)),
+        };
+        let rgba = itertools::izip!(red.r_iter(), green.r_iter(), blue.r_iter(), alpha)
+            .flat_map(|(r, g, b, a)| [r, g, b, a])
+            .collect();
+
+        CanvasImage::from_vec_with_size(rgba, width, height)
+    }
+
+    /// Rearrange the channels, `order` names the source of the red, green, blue and alpha
+    /// output in turn: one of `r`, `g`, `b`, `a`, or `0` and `1` for black and white. E.g.
+    /// `"bgra"` swaps red and blue and `"rgb1"` drops the transparency.
+    pub fn swizzle(&self, order: &str) -> color_eyre::Result<CanvasImage> {
+        let sources = order
+            .chars()
+            .map(|c| Source::parse(c).ok_or_else(|| eyre!("{c} is not r, g, b, a, 0 or 1")))
+            .collect::<color_eyre::Result<Vec<_>>>()?;
+        let Ok(sources) = <[Source; 4]>::try_from(sources) else {
+            bail!("the order must name exactly 4 channels, got {order}");
+        };
+
+        let mut data = self.data.clone();
+        for pixel in data.chunks_exact_mut(4) {
+            let original = [pixel[0], pixel[1], pixel[2], pixel[3]];
+            for (out, source) in pixel.iter_mut().zip(sources) {
+                *out = match source {
+                    Source::Channel(channel) => original[channel.offset()],
+                    Source::Constant(v) => v,
+                };
+            }
+        }
+
+        Ok(CanvasImage::from_vec_with_size(
+            data,
+            self.width,
+            self.height,
+        ))
+    }
+
+    /// Color matrix like the SVG/CSS `feColorMatrix`: every output channel is a row of
+    /// `matrix`, four weights for the red, green, blue and alpha input and an offset, with all
+    /// values in [0, 1]
+    pub fn channel_mix(&self, matrix: &[f64; 20]) -> CanvasImage {
+        let mut data = self.data.clone();
+        for pixel in data.chunks_exact_mut(4) {
+            let input = [0, 1, 2, 3].map(|c| pixel[c] as f64 / 255.0);
+            for (out, row) in pixel.iter_mut().zip(matrix.chunks_exact(5)) {
+                let value = row[..4].iter().zip(input).map(|(m, v)| m * v).sum::<f64>() + row[4];
+                *out = (value * 255.0).round().clamp(0.0, 255.0) as u8;
+            }
+        }
+
+        CanvasImage::from_vec_with_size(data, self.width, self.height)
+    }
+}
+
+#[wasm_bindgen]
+pub fn extract_channel(image: ImageData, channel: Channel) -> ImageData {
+    let image = CanvasImage::from_image_data(image);
+    image.extract_channel(channel).into()
+}
+
+#[wasm_bindgen]
+pub fn merge_channels(
+    red: ImageData,
+    green: ImageData,
+    blue: ImageData,
+    alpha: Option<ImageData>,
+) -> Result<ImageData, String> {
+    let [red, green, blue] = [red, green, blue].map(CanvasImage::from_image_data);
+    let alpha = alpha.map(CanvasImage::from_image_data);
+
+    let size = (red.width, red.height);
+    let sizes = [Some(&green), Some(&blue), alpha.as_ref()];
+    if sizes
+        .into_iter()
+        .flatten()
+        .any(|c| (c.width, c.height) != size)
+    {
+        return Err("all channels must be the same size".to_string());
+    }
+
+    Ok(CanvasImage::merge_channels(&red, &green, &blue, alpha.as_ref()).into())
+}
+
+#[wasm_bindgen]
+pub fn swizzle(image: ImageData, order: &str) -> Result<ImageData, String> {
+    let image = CanvasImage::from_image_data(image);
+    let swizzled = image.swizzle(order).map_err(|e| e.to_string())?;
+    Ok(swizzled.into())
+}
+
+/// # Arguments
+/// * matrix: the 4x5 color matrix row by row, as in `feColorMatrix`
+#[wasm_bindgen]
+pub fn channel_mix(image: ImageData, matrix: &[f64]) -> Result<ImageData, String> {
+    let matrix: &[f64; 20] = matrix
+        .try_into()
+        .map_err(|_| format!("expected 20 numbers, got {}", matrix.len()))?;
+    let image = CanvasImage::from_image_data(image);
+    Ok(image.channel_mix(matrix).into())
+}
+
+#[wasm_bindgen]
+pub fn channel_mix_preset(image: ImageData, preset: ColorMatrixPreset) -> ImageData {
+    let image = CanvasImage::from_image_data(image);
+    image.channel_mix(&preset.matrix()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image() -> CanvasImage {
+        CanvasImage::from_vec_with_size(vec![10, 20, 30, 40, 200, 150, 100, 255], 2, 1)
+    }
+
+    #[test]
+    fn extract_swizzle_and_merge() {
+        let image = image();
+
+        let green = image.extract_channel(Channel::Green);
+        assert_eq!(green.rgba(0, 0), Some((20, 20, 20, 255)));
+
+        let [r, g, b, a] = [Channel::Red, Channel::Green, Channel::Blue, Channel::Alpha]
+            .map(|channel| image.extract_channel(channel));
+        let merged = CanvasImage::merge_channels(&r, &g, &b, Some(&a));
+        assert_eq!(merged.rgba_slice(), image.rgba_slice());
+        let opaque = CanvasImage::merge_channels(&b, &g, &r, None);
+        assert_eq!(opaque.rgba(0, 0), Some((30, 20, 10, 255)));
+
+        assert_eq!(
+            image.swizzle("bgra").unwrap().rgba(0, 0),
+            Some((30, 20, 10, 40))
+        );
+        assert_eq!(
+            image.swizzle("RRG1").unwrap().rgba(1, 0),
+            Some((200, 200, 150, 255))
+        );
+        assert!(image.swizzle("rgb").is_err());
+        assert!(image.swizzle("rgbx").is_err());
+    }
+
+    #[test]
+    fn color_matrices() {
+        let image = image();
+
+        #[rustfmt::skip]
+        let identity = [
+            1.0, 0.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 0.0, 1.0, 0.0,
+        ];
+        assert_eq!(
+            image.channel_mix(&identity).rgba_slice(),
+            image.rgba_slice()
+        );
+
+        let inverted = image.channel_mix(&ColorMatrixPreset::Invert.matrix());
+        assert_eq!(inverted.rgba(0, 0), Some((245, 235, 225, 40)));
+
+        let grey = image.channel_mix(&ColorMatrixPreset::Grayscale.matrix());
+        assert_eq!(grey.rgba(1, 0), Some((157, 157, 157, 255)));
+
+        // sepia pushes white past the top, which is clamped
+        let white = CanvasImage::from_vec_with_size(vec![255; 4], 1, 1);
+        let sepia = white.channel_mix(&ColorMatrixPreset::Sepia.matrix());
+        assert_eq!(sepia.rgba(0, 0), Some((255, 255, 239, 255)));
+    }
+}
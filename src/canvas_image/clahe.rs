@@ -0,0 +1,197 @@
+use super::*;
+
+/// Equalization mapping of one tile: the histogram is clipped at `clip_limit` times the mean
+/// bucket count, and what was cut off is spread evenly over all buckets so the mapping can't get
+/// steeper than the limit allows
+fn tile_mapping(values: impl Iterator<Item = u8>, clip_limit: f64) -> [f64; 256] {
+    let mut histogram = [0f64; 256];
+    let mut count = 0.0;
+    for v in values {
+        histogram[v as usize] += 1.0;
+        count += 1.0;
+    }
+
+    let clip = (clip_limit * count / 256.0).max(1.0);
+    let excess: f64 = histogram.iter().map(|h| (h - clip).max(0.0)).sum();
+    for h in histogram.iter_mut() {
+        *h = h.min(clip) + excess / 256.0;
+    }
+
+    let mut mapping = [0f64; 256];
+    let mut sum = 0.0;
+    for (m, h) in mapping.iter_mut().zip(histogram) {
+        sum += h;
+        *m = sum / count * 255.0;
+    }
+
+    mapping
+}
+
+/// CLAHE on a single plane of 8 bit values, the result is in [0, 255]
+fn clahe_plane(
+    values: &[u8],
+    width: u32,
+    height: u32,
+    (columns, rows): (u32, u32),
+    clip_limit: f64,
+) -> Vec<f64> {
+    if width == 0 || height == 0 {
+        return Vec::new();
+    }
+
+    let columns = columns.clamp(1, width);
+    let rows = rows.clamp(1, height);
+    let (tile_width, tile_height) = (width as f64 / columns as f64, height as f64 / rows as f64);
+
+    // tile (i, j) covers the pixels from i * width / columns up to (i + 1) * width / columns
+    let bounds = |i: u32, tiles: u32, size: u32| (i * size / tiles)..((i + 1) * size / tiles);
+    let mappings: Vec<[f64; 256]> = iproduct!(0..rows, 0..columns)
+        .map(|(j, i)| {
+            let pixels = iproduct!(bounds(j, rows, height), bounds(i, columns, width))
+                .map(|(y, x)| values[(y * width + x) as usize]);
+            tile_mapping(pixels, clip_limit)
+        })
+        .collect();
+
+    // the two tiles whose centers are on either side of `position`, and how far it is between
+    let neighbours = |position: f64, tile_size: f64, tiles: u32| {
+        let t = (position + 0.5) / tile_size - 0.5;
+        let first = (t.floor().max(0.0) as u32).min(tiles - 1);
+        let second = (first + 1).min(tiles - 1);
+        (first, second, (t - first as f64).clamp(0.0, 1.0))
+    };
+
+    iproduct!(0..height, 0..width)
+        .map(|(y, x)| {
+            let v = values[(y * width + x) as usize] as usize;
+            let (left, right, fx) = neighbours(x as f64, tile_width, columns);
+            let (top, bottom, fy) = neighbours(y as f64, tile_height, rows);
+            let map = |i: u32, j: u32| mappings[(j * columns + i) as usize][v];
+
+            let upper = map(left, top) * (1.0 - fx) + map(right, top) * fx;
+            let lower = map(left, bottom) * (1.0 - fx) + map(right, bottom) * fx;
+            upper * (1.0 - fy) + lower * fy
+        })
+        .collect()
+}
+
+impl CanvasImage {
+    /// Contrast limited adaptive histogram equalization on the lightness, see `clahe_with`
+    pub fn clahe(&self, tile_grid: (u32, u32), clip_limit: f64) -> CanvasImage {
+        self.clahe_with(tile_grid, clip_limit, HistogramChannels::Lightness)
+    }
+
+    /// Contrast limited adaptive histogram equalization: the image is split into a grid of
+    /// tiles that are equalized on their own, with the histograms clipped so noise in flat areas
+    /// isn't blown up, and every pixel blends the mappings of the four closest tiles so the tile
+    /// borders don't show
+    ///
+    /// # Arguments
+    /// * tile_grid: how many tiles across and down, 8 by 8 is a common choice
+    /// * clip_limit: how far a bucket can go above the mean bucket count, larger values allow
+    ///   more contrast, 2 to 4 works well
+    pub fn clahe_with(
+        &self,
+        tile_grid: (u32, u32),
+        clip_limit: f64,
+        channels: HistogramChannels,
+    ) -> CanvasImage {
+        let (width, height) = (self.width, self.height);
+
+        match channels {
+            HistogramChannels::Rgb => {
+                let mut equalized =
+                    CanvasImage::from_vec_with_size(self.data.clone(), width, height);
+                for channel in Channel::COLOR {
+                    let values: Vec<u8> = self.channel_iter(channel).collect();
+                    let levels = clahe_plane(&values, width, height, tile_grid, clip_limit);
+                    let plane = levels.into_iter().map(|v| v / 255.0).collect();
+                    equalized.set_plane(
+                        channel,
+                        &FloatImage::from_vec_with_size(plane, width, height),
+                    );
+                }
+
+                equalized
+            }
+            HistogramChannels::Lightness => {
+                let (planes, levels) = self.lightness_levels();
+                let levels = clahe_plane(&levels, width, height, tile_grid, clip_limit);
+                self.with_lightness_levels(planes, levels)
+            }
+        }
+    }
+}
+
+#[wasm_bindgen]
+pub fn clahe(
+    image: ImageData,
+    tiles_across: u32,
+    tiles_down: u32,
+    clip_limit: f64,
+    channels: HistogramChannels,
+) -> ImageData {
+    let image = CanvasImage::from_image_data(image);
+    image
+        .clahe_with((tiles_across, tiles_down), clip_limit, channels)
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clip_limit_bounds_the_contrast() {
+        // a faint ramp, 16 values repeated over 64 by 64 pixels
+        let values: Vec<u8> = (0..64 * 64).map(|i| 100 + (i % 16) as u8).collect();
+        let clahe = |clip_limit| clahe_plane(&values, 64, 64, (2, 2), clip_limit);
+
+        // the spread of the 16 values grows with the limit, plain equalization stretches them
+        // over the whole range
+        let spread = |levels: Vec<f64>| levels[15] - levels[0];
+        assert!(spread(clahe(1.0)) < 2.5 * 15.0);
+        assert!(spread(clahe(1.0)) < spread(clahe(4.0)));
+        assert!(spread(clahe(4.0)) < spread(clahe(100.0)));
+        assert!(spread(clahe(100.0)) > 200.0);
+    }
+
+    #[test]
+    fn equalizes_locally() {
+        // a dark and a bright half, each with a little texture
+        let rgba: Vec<u8> = iproduct!(0..8, 0..16)
+            .flat_map(|(y, x)| {
+                let base = if x < 8 { 20 } else { 200 };
+                let v = base + ((x + y) % 2) * 10;
+                [v, v, v, 200]
+            })
+            .collect();
+        let image = CanvasImage::from_vec_with_size(rgba, 16, 8);
+
+        let equalized = image.clahe((2, 1), 40.0);
+        let contrast = |x| {
+            equalized
+                .r(x + 1, 0)
+                .unwrap()
+                .abs_diff(equalized.r(x, 0).unwrap())
+        };
+        assert!(contrast(1) > 40);
+        assert!(contrast(13) > 40);
+
+        let (r, g, b, a) = equalized.rgba(3, 3).unwrap();
+        assert!(r == g && g == b && a == 200);
+
+        let rgb = image.clahe_with((2, 1), 40.0, HistogramChannels::Rgb);
+        assert!(rgb.r(2, 0).unwrap().abs_diff(rgb.r(1, 0).unwrap()) > 40);
+    }
+
+    #[test]
+    fn empty_images() {
+        assert!(clahe_plane(&[], 0, 0, (8, 8), 2.0).is_empty());
+
+        let empty = CanvasImage::from_vec_with_size(Vec::new(), 0, 4);
+        assert!(empty.clahe((8, 8), 2.0).rgba_slice().is_empty());
+        let rgb = empty.clahe_with((8, 8), 2.0, HistogramChannels::Rgb);
+        assert!(rgb.rgba_slice().is_empty());
+    }
+}
@@ -0,0 +1,371 @@
+use super::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Which neighbours of a pixel count as touching it
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Connectivity {
+    /// Left, right, above and below
+    Four,
+    /// Diagonals as well
+    Eight,
+}
+
+impl Connectivity {
+    /// The neighbours that come before the pixel in raster order
+    fn previous(&self) -> &'static [(i64, i64)] {
+        match self {
+            Connectivity::Four => &[(-1, 0), (0, -1)],
+            Connectivity::Eight => &[(-1, 0), (-1, -1), (0, -1), (1, -1)],
+        }
+    }
+}
+
+/// Disjoint sets over provisional labels, with path halving
+struct UnionFind {
+    parents: Vec<u32>,
+}
+
+impl UnionFind {
+    fn make_set(&mut self) -> u32 {
+        self.parents.push(self.parents.len() as u32);
+        self.parents.len() as u32 - 1
+    }
+
+    fn find(&mut self, mut label: u32) -> u32 {
+        while self.parents[label as usize] != label {
+            let grandparent = self.parents[self.parents[label as usize] as usize];
+            self.parents[label as usize] = grandparent;
+            label = grandparent;
+        }
+
+        label
+    }
+
+    /// Merge the two sets, the smaller root becomes the root of both
+    fn union(&mut self, a: u32, b: u32) {
+        let (a, b) = (self.find(a), self.find(b));
+        self.parents[a.max(b) as usize] = a.min(b);
+    }
+}
+
+/// Measurements of one connected component
+#[derive(Debug, Clone, PartialEq)]
+pub struct Region {
+    pub label: u32,
+    /// Number of pixels
+    pub area: u32,
+    /// Mean (x, y) of the pixels
+    pub centroid: (f64, f64),
+    pub bounding_box: BoundingBox,
+    /// Mean RGBA of the pixels, in [0, 255]
+    pub mean_color: [f64; 4],
+    /// Eccentricity of the ellipse with the same second moments, 0 for a disk and close to 1
+    /// for a thin line
+    pub eccentricity: f64,
+}
+
+/// The connected components of a binary image
+#[derive(Debug, Clone, PartialEq)]
+pub struct Components {
+    /// Label of every pixel, 0 is the background and the components are numbered from 1 in the
+    /// order they are met scanning from the top left
+    pub labels: Vec<u32>,
+    pub width: u32,
+    pub height: u32,
+    /// One per component, `regions[i]` has label `i + 1`
+    pub regions: Vec<Region>,
+}
+
+impl Components {
+    pub fn count(&self) -> usize {
+        self.regions.len()
+    }
+
+    /// Measure the components on `image`, which has to be the same size as the labeled one. The
+    /// mean colors are the ones of `image`, e.g. the photo a mask was thresholded from.
+    pub fn measure(&self, image: &CanvasImage) -> Vec<Region> {
+        assert_eq!(image.horizontal_size(), self.width);
+        assert_eq!(image.vertical_size(), self.height);
+
+        #[derive(Default, Clone)]
+        struct Moments {
+            area: f64,
+            sum: (f64, f64),
+            squares: (f64, f64, f64),
+            color: [f64; 4],
+            /// Smallest and largest x and y seen so far
+            corners: Option<(u32, u32, u32, u32)>,
+        }
+
+        let count = self.labels.iter().max().copied().unwrap_or(0) as usize;
+        let mut moments = vec![Moments::default(); count];
+        for (i, label) in self.labels.iter().enumerate() {
+            if *label == 0 {
+                continue;
+            }
+
+            let (x, y) = (i as u32 % self.width, i as u32 / self.width);
+            let (fx, fy) = (x as f64, y as f64);
+            let m = &mut moments[*label as usize - 1];
+
+            m.area += 1.0;
+            m.sum = (m.sum.0 + fx, m.sum.1 + fy);
+            m.squares = (
+                m.squares.0 + fx * fx,
+                m.squares.1 + fx * fy,
+                m.squares.2 + fy * fy,
+            );
+            for (sum, v) in m.color.iter_mut().zip(&image.data[i * 4..i * 4 + 4]) {
+                *sum += *v as f64;
+            }
+            m.corners = Some(match m.corners {
+                Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+                None => (x, y, x, y),
+            });
+        }
+
+        moments
+            .into_iter()
+            .enumerate()
+            .map(|(i, m)| {
+                let (cx, cy) = (m.sum.0 / m.area, m.sum.1 / m.area);
+
+                // central second moments and the eigenvalues of their covariance matrix
+                let xx = m.squares.0 / m.area - cx * cx;
+                let xy = m.squares.1 / m.area - cx * cy;
+                let yy = m.squares.2 / m.area - cy * cy;
+                let spread = f64::hypot((xx - yy) / 2.0, xy);
+                let (major, minor) = ((xx + yy) / 2.0 + spread, (xx + yy) / 2.0 - spread);
+
+                let eccentricity = if major > 0.0 {
+                    (1.0 - (minor / major).max(0.0)).sqrt()
+                } else {
+                    0.0
+                };
+
+                let (x0, y0, x1, y1) = m.corners.unwrap();
+                Region {
+                    label: i as u32 + 1,
+                    area: m.area as u32,
+                    centroid: (cx, cy),
+                    bounding_box: BoundingBox {
+                        x: x0,
+                        y: y0,
+                        width: x1 - x0 + 1,
+                        height: y1 - y0 + 1,
+                    },
+                    mean_color: m.color.map(|c| c / m.area),
+                    eccentricity,
+                }
+            })
+            .collect()
+    }
+
+    /// Paint every component in a random color on a black background, the same `seed` always
+    /// gives the same colors
+    pub fn to_color(&self, seed: u64) -> CanvasImage {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let palette: Vec<[u8; 4]> = std::iter::once([0, 0, 0, 255])
+            .chain((0..self.count()).map(|_| {
+                // keep the colors away from black so small components stay visible
+                let [r, g, b] = [0; 3].map(|_| rng.gen_range(64..=255));
+                [r, g, b, 255]
+            }))
+            .collect();
+
+        let rgba = self
+            .labels
+            .iter()
+            .flat_map(|label| palette[*label as usize])
+            .collect();
+        CanvasImage::from_vec_with_size(rgba, self.width, self.height)
+    }
+}
+
+impl CanvasImage {
+    /// Label the connected foreground regions of the image, treated as binary, with the classic
+    /// two pass algorithm: provisional labels and their equivalences are collected in one scan
+    /// and resolved through union-find in the second
+    pub fn connected_components(&self, connectivity: Connectivity) -> Components {
+        let foreground = self.foreground();
+        let (width, height) = (self.width as i64, self.height as i64);
+        let index = |x: i64, y: i64| (y * width + x) as usize;
+
+        // provisional label 0 is the background
+        let mut sets = UnionFind { parents: vec![0] };
+        let mut labels = vec![0u32; foreground.len()];
+
+        for (y, x) in iproduct!(0..height, 0..width) {
+            if !foreground[index(x, y)] {
+                continue;
+            }
+
+            let neighbours = connectivity
+                .previous()
+                .iter()
+                .map(|(dx, dy)| (x + dx, y + dy))
+                .filter(|(x, y)| (0..width).contains(x) && (0..height).contains(y))
+                .map(|(x, y)| labels[index(x, y)])
+                .filter(|label| *label != 0);
+
+            let mut label = 0;
+            for neighbour in neighbours {
+                if label == 0 {
+                    label = neighbour;
+                } else {
+                    sets.union(label, neighbour);
+                }
+            }
+
+            labels[index(x, y)] = if label == 0 { sets.make_set() } else { label };
+        }
+
+        // number the roots in the order they were created, which is raster order
+        let mut compact = vec![0u32; sets.parents.len()];
+        let mut count = 0;
+        for label in 1..sets.parents.len() as u32 {
+            let root = sets.find(label);
+            if root == label {
+                count += 1;
+                compact[label as usize] = count;
+            } else {
+                compact[label as usize] = compact[root as usize];
+            }
+        }
+        labels
+            .iter_mut()
+            .for_each(|label| *label = compact[*label as usize]);
+
+        let mut components = Components {
+            labels,
+            width: self.width,
+            height: self.height,
+            regions: Vec::new(),
+        };
+        components.regions = components.measure(self);
+
+        components
+    }
+}
+
+/// Paint every connected component of the binary image in a random color
+#[wasm_bindgen]
+pub fn connected_components(image: ImageData, connectivity: Connectivity, seed: u32) -> ImageData {
+    let image = CanvasImage::from_image_data(image);
+    image
+        .connected_components(connectivity)
+        .to_color(seed as u64)
+        .into()
+}
+
+/// # Arguments
+/// * mask: the binary image whose components are measured
+/// * photo: the image the mean colors are taken from, the same size as `mask`
+///
+/// # Returns
+///  11 numbers per component, in label order: area, centroid x, centroid y, bounding box x, y,
+///  width, height, mean red, green, blue and eccentricity
+#[wasm_bindgen]
+pub fn component_stats(
+    mask: ImageData,
+    photo: ImageData,
+    connectivity: Connectivity,
+) -> Result<Vec<f64>, String> {
+    let mask = CanvasImage::from_image_data(mask);
+    let photo = CanvasImage::from_image_data(photo);
+    if (mask.width, mask.height) != (photo.width, photo.height) {
+        return Err("the mask and the photo must be the same size".to_string());
+    }
+
+    let stats = mask
+        .connected_components(connectivity)
+        .measure(&photo)
+        .iter()
+        .flat_map(|r| {
+            let b = r.bounding_box;
+            [
+                r.area as f64,
+                r.centroid.0,
+                r.centroid.1,
+                b.x as f64,
+                b.y as f64,
+                b.width as f64,
+                b.height as f64,
+                r.mean_color[0],
+                r.mean_color[1],
+                r.mean_color[2],
+                r.eccentricity,
+            ]
+        })
+        .collect();
+
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas_image::test_util::binary;
+
+    #[test]
+    fn connectivity_decides_what_touches() {
+        // a U whose arms only meet in the last row, and a diagonal pair
+        let image = binary(&["#.#...#.", "#.#....#", "###....."]);
+
+        let four = image.connected_components(Connectivity::Four);
+        assert_eq!(four.count(), 3);
+        assert_eq!(four.labels[..8], [1, 0, 1, 0, 0, 0, 2, 0]);
+        assert_eq!(four.labels[8..16], [1, 0, 1, 0, 0, 0, 0, 3]);
+
+        let eight = image.connected_components(Connectivity::Eight);
+        assert_eq!(eight.count(), 2);
+        assert_eq!(eight.labels[7 + 8], 2);
+    }
+
+    #[test]
+    fn region_properties() {
+        let image = binary(&["......", ".####.", "......", ".##...", ".##..."]);
+        let components = image.connected_components(Connectivity::Four);
+        let (bar, square) = (&components.regions[0], &components.regions[1]);
+
+        assert_eq!(bar.area, 4);
+        assert_eq!(bar.centroid, (2.5, 1.0));
+        assert_eq!(
+            bar.bounding_box,
+            BoundingBox {
+                x: 1,
+                y: 1,
+                width: 4,
+                height: 1
+            }
+        );
+        assert_eq!(bar.eccentricity, 1.0);
+
+        assert_eq!(square.area, 4);
+        assert_eq!(square.centroid, (1.5, 3.5));
+        assert_eq!(square.eccentricity, 0.0);
+
+        // the background stays black, both components get a color
+        let colored = components.to_color(1);
+        assert_eq!(colored.rgba(0, 0), Some((0, 0, 0, 255)));
+        assert!(colored.r(1, 1).unwrap() >= 64);
+    }
+
+    #[test]
+    fn colors_come_from_the_photo() {
+        let mask = binary(&["##..", "...#"]);
+        #[rustfmt::skip]
+        let photo = CanvasImage::from_vec_with_size(vec![
+            10, 20, 30, 255,   30, 40, 50, 255,   0, 0, 0, 255,   0, 0, 0, 255,
+             0,  0,  0, 255,    0,  0,  0, 255,   0, 0, 0, 255,   7, 8, 9, 100,
+        ], 4, 2);
+
+        let regions = mask
+            .connected_components(Connectivity::Four)
+            .measure(&photo);
+        assert_eq!(regions[0].mean_color, [20.0, 30.0, 40.0, 255.0]);
+        assert_eq!(regions[1].mean_color, [7.0, 8.0, 9.0, 100.0]);
+    }
+}
@@ -0,0 +1,427 @@
+use super::*;
+use std::fmt::Write;
+
+/// The 8 neighbours of a pixel as (dx, dy), going counterclockwise (as seen on screen, with y
+/// pointing down) starting from the right
+const DIRECTIONS: [(i64, i64); 8] = [
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+/// Smallest axis aligned rectangle that contains a shape, in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BoundingBox {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl BoundingBox {
+    /// The bounding box of a non empty set of pixels
+    pub fn of(pixels: impl IntoIterator<Item = (u32, u32)>) -> Option<BoundingBox> {
+        let (x0, y0, x1, y1) = pixels
+            .into_iter()
+            .fold((u32::MAX, u32::MAX, 0, 0), |(x0, y0, x1, y1), (x, y)| {
+                (x0.min(x), y0.min(y), x1.max(x), y1.max(y))
+            });
+
+        (x0 <= x1).then_some(BoundingBox {
+            x: x0,
+            y: y0,
+            width: x1 - x0 + 1,
+            height: y1 - y0 + 1,
+        })
+    }
+}
+
+/// A border between foreground and background, as the closed polygon through the centers of the
+/// foreground pixels on it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Contour {
+    pub points: Vec<(u32, u32)>,
+    /// Whether this is the border of a hole inside a foreground region rather than the outer
+    /// border of the region
+    pub is_hole: bool,
+    /// Index of the contour directly enclosing this one, among the contours it was found with
+    pub parent: Option<usize>,
+}
+
+impl Contour {
+    /// Area enclosed by the polygon (shoelace formula)
+    pub fn area(&self) -> f64 {
+        let twice: i64 = self
+            .edges()
+            .map(|((x0, y0), (x1, y1))| x0 as i64 * y1 as i64 - x1 as i64 * y0 as i64)
+            .sum();
+
+        twice.abs() as f64 / 2.0
+    }
+
+    /// Length of the closed polygon
+    pub fn perimeter(&self) -> f64 {
+        self.edges()
+            .map(|((x0, y0), (x1, y1))| f64::hypot(x1 as f64 - x0 as f64, y1 as f64 - y0 as f64))
+            .sum()
+    }
+
+    pub fn bounding_box(&self) -> BoundingBox {
+        BoundingBox::of(self.points.iter().copied()).expect("a contour is never empty")
+    }
+
+    /// The convex hull of the points (Andrew's monotone chain), counterclockwise on screen
+    pub fn convex_hull(&self) -> Vec<(u32, u32)> {
+        let mut points = self.points.clone();
+        points.sort_by_key(|(x, y)| (*x, *y));
+        points.dedup();
+
+        if points.len() < 3 {
+            return points;
+        }
+
+        let cross = |o: (u32, u32), a: (u32, u32), b: (u32, u32)| {
+            let (ox, oy) = (o.0 as i64, o.1 as i64);
+            (a.0 as i64 - ox) * (b.1 as i64 - oy) - (a.1 as i64 - oy) * (b.0 as i64 - ox)
+        };
+
+        let mut hull: Vec<(u32, u32)> = Vec::with_capacity(2 * points.len());
+        for pass in [points.clone(), points.into_iter().rev().collect()] {
+            let start = hull.len();
+            for point in pass {
+                while hull.len() >= start + 2
+                    && cross(hull[hull.len() - 2], hull[hull.len() - 1], point) >= 0
+                {
+                    hull.pop();
+                }
+                hull.push(point);
+            }
+            // the last point of each half is the first of the other
+            hull.pop();
+        }
+
+        hull
+    }
+
+    /// Simplify the polygon with the Douglas-Peucker algorithm, no point of the original is
+    /// further than `epsilon` pixels from the simplified one
+    pub fn simplify(&self, epsilon: f64) -> Contour {
+        if self.points.len() < 3 {
+            return self.clone();
+        }
+
+        // split the closed polygon into two open chains at the point furthest from the first
+        let first = self.points[0];
+        let distance =
+            |p: &(u32, u32)| f64::hypot(p.0 as f64 - first.0 as f64, p.1 as f64 - first.1 as f64);
+        let (split, _) = self
+            .points
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| distance(a).total_cmp(&distance(b)))
+            .unwrap();
+
+        let mut closed = self.points.clone();
+        closed.push(first);
+
+        let mut points = douglas_peucker(&closed[..=split], epsilon);
+        points.pop();
+        points.extend(douglas_peucker(&closed[split..], epsilon));
+        points.pop();
+
+        Contour {
+            points,
+            ..self.clone()
+        }
+    }
+
+    /// The polygon as SVG path data, e.g. `M 1 1 L 4 1 L 4 3 Z`
+    pub fn to_svg_path(&self) -> String {
+        let mut path = String::new();
+        for (i, (x, y)) in self.points.iter().enumerate() {
+            let command = if i == 0 { "M" } else { " L" };
+            write!(path, "{command} {x} {y}").unwrap();
+        }
+        path.push_str(" Z");
+
+        path
+    }
+
+    /// Consecutive pairs of points, including the one closing the polygon
+    fn edges(&self) -> impl Iterator<Item = ((u32, u32), (u32, u32))> + '_ {
+        let closing = self.points.iter().skip(1).chain(self.points.first());
+        Iterator::zip(self.points.iter().copied(), closing.copied())
+    }
+}
+
+/// Path data of all the contours together, with the even-odd fill rule the holes stay holes
+pub fn contours_to_svg_path(contours: &[Contour]) -> String {
+    contours
+        .iter()
+        .map(Contour::to_svg_path)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Douglas-Peucker on an open chain, keeps both of its ends
+fn douglas_peucker(chain: &[(u32, u32)], epsilon: f64) -> Vec<(u32, u32)> {
+    let (first, last) = (chain[0], chain[chain.len() - 1]);
+    let (fx, fy) = (first.0 as f64, first.1 as f64);
+    let (dx, dy) = (last.0 as f64 - fx, last.1 as f64 - fy);
+    let length = f64::hypot(dx, dy);
+
+    let distance = |(x, y): (u32, u32)| {
+        let (px, py) = (x as f64 - fx, y as f64 - fy);
+        if length == 0.0 {
+            f64::hypot(px, py)
+        } else {
+            (px * dy - py * dx).abs() / length
+        }
+    };
+
+    let furthest = chain
+        .iter()
+        .enumerate()
+        .skip(1)
+        .take(chain.len().saturating_sub(2))
+        .map(|(i, p)| (i, distance(*p)))
+        .max_by(|a, b| a.1.total_cmp(&b.1));
+
+    match furthest {
+        Some((i, d)) if d > epsilon => {
+            let mut points = douglas_peucker(&chain[..=i], epsilon);
+            points.pop();
+            points.extend(douglas_peucker(&chain[i..], epsilon));
+            points
+        }
+        _ if first == last => vec![first],
+        _ => vec![first, last],
+    }
+}
+
+impl CanvasImage {
+    /// Trace the borders of the foreground regions with Suzuki and Abe's border following,
+    /// treating the image as binary. Regions are 8-connected, holes 4-connected.
+    ///
+    /// # Returns
+    ///  the outer borders and hole borders in the order they are met scanning the image from the
+    ///  top left, every contour knows the one directly enclosing it
+    pub fn find_contours(&self) -> Vec<Contour> {
+        // pad with a background frame so the border following never leaves the image
+        let (width, height) = (self.width as i64 + 2, self.height as i64 + 2);
+        let foreground = self.foreground();
+        let mut f: Vec<i64> = iproduct!(0..height, 0..width)
+            .map(|(y, x)| {
+                let inside = (1..width - 1).contains(&x) && (1..height - 1).contains(&y);
+                let index = ((y - 1) * self.width as i64 + x - 1) as usize;
+                (inside && foreground[index]) as i64
+            })
+            .collect();
+        let at = |x: i64, y: i64| (y * width + x) as usize;
+
+        // border 1 is the frame, an imaginary hole border around everything
+        let mut contours: Vec<Contour> = Vec::new();
+        let border = |nbd: i64| (nbd - 2) as usize;
+
+        for y in 1..height - 1 {
+            let mut lnbd = 1;
+            for x in 1..width - 1 {
+                let value = f[at(x, y)];
+                let start = if value == 1 && f[at(x - 1, y)] == 0 {
+                    Some((false, (x - 1, y)))
+                } else if value >= 1 && f[at(x + 1, y)] == 0 {
+                    if value > 1 {
+                        lnbd = value;
+                    }
+                    Some((true, (x + 1, y)))
+                } else {
+                    None
+                };
+
+                if let Some((is_hole, from)) = start {
+                    let nbd = contours.len() as i64 + 2;
+
+                    // the border met last is either a sibling or the parent of the new one
+                    let parent = if lnbd == 1 {
+                        None
+                    } else {
+                        let last = &contours[border(lnbd)];
+                        if last.is_hole == is_hole {
+                            last.parent
+                        } else {
+                            Some(border(lnbd))
+                        }
+                    };
+
+                    let points = follow_border(&mut f, width, (x, y), from, nbd)
+                        .into_iter()
+                        .map(|(x, y)| ((x - 1) as u32, (y - 1) as u32))
+                        .collect();
+
+                    contours.push(Contour {
+                        points,
+                        is_hole,
+                        parent,
+                    });
+                }
+
+                let value = f[at(x, y)];
+                if value != 1 && value != 0 {
+                    lnbd = value.abs();
+                }
+            }
+        }
+
+        contours
+    }
+}
+
+/// Follow the border starting at `start` (step 3 of Suzuki and Abe), marking it with `nbd` in
+/// `f`, `from` is the background pixel the border was entered from
+fn follow_border(
+    f: &mut [i64],
+    width: i64,
+    start: (i64, i64),
+    from: (i64, i64),
+    nbd: i64,
+) -> Vec<(i64, i64)> {
+    let at = |(x, y): (i64, i64)| (y * width + x) as usize;
+    let direction = |from: (i64, i64), to: (i64, i64)| {
+        DIRECTIONS
+            .iter()
+            .position(|d| *d == (to.0 - from.0, to.1 - from.1))
+            .unwrap()
+    };
+    let step = |p: (i64, i64), d: usize| (p.0 + DIRECTIONS[d].0, p.1 + DIRECTIONS[d].1);
+
+    // clockwise from `from` for the first foreground neighbour
+    let first_direction = direction(start, from);
+    let first = (0..8)
+        .map(|i| (first_direction + 8 - i) % 8)
+        .map(|d| step(start, d))
+        .find(|p| f[at(*p)] != 0);
+
+    let Some(first) = first else {
+        // an isolated pixel
+        f[at(start)] = -nbd;
+        return vec![start];
+    };
+
+    let mut points = Vec::new();
+    let (mut previous, mut current) = (first, start);
+    loop {
+        points.push(current);
+
+        // counterclockwise from the one after `previous` for the next foreground neighbour
+        let from_direction = direction(current, previous);
+        let mut right_is_background = false;
+        let mut next = current;
+        for i in 1..=8 {
+            let d = (from_direction + i) % 8;
+            let candidate = step(current, d);
+            if f[at(candidate)] != 0 {
+                next = candidate;
+                break;
+            }
+            if d == 0 {
+                right_is_background = true;
+            }
+        }
+
+        if right_is_background {
+            f[at(current)] = -nbd;
+        } else if f[at(current)] == 1 {
+            f[at(current)] = nbd;
+        }
+
+        if next == start && current == first {
+            break;
+        }
+        (previous, current) = (current, next);
+    }
+
+    points
+}
+
+/// Trace the contours of the foreground and simplify them
+///
+/// # Returns
+///  SVG path data of all the contours, to be filled with the even-odd rule
+#[wasm_bindgen]
+pub fn contours_svg_path(image: ImageData, epsilon: f64) -> String {
+    let image = CanvasImage::from_image_data(image);
+    let contours: Vec<_> = image
+        .find_contours()
+        .iter()
+        .map(|contour| contour.simplify(epsilon))
+        .collect();
+
+    contours_to_svg_path(&contours)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas_image::test_util::binary;
+
+    #[test]
+    fn ring_and_island() {
+        let image = binary(&[
+            "..........",
+            ".#####....",
+            ".#...#..#.",
+            ".#...#....",
+            ".#####....",
+            "..........",
+        ]);
+        let contours = image.find_contours();
+
+        assert_eq!(contours.len(), 3);
+        let (outer, hole, island) = (&contours[0], &contours[1], &contours[2]);
+
+        assert!(!outer.is_hole && outer.parent.is_none());
+        assert_eq!(outer.points.len(), 14);
+        assert_eq!(outer.area(), 12.0);
+        assert_eq!(outer.perimeter(), 14.0);
+        assert_eq!(
+            outer.bounding_box(),
+            BoundingBox {
+                x: 1,
+                y: 1,
+                width: 5,
+                height: 4
+            }
+        );
+
+        assert!(hole.is_hole);
+        assert_eq!(hole.parent, Some(0));
+
+        assert!(!island.is_hole && island.parent.is_none());
+        assert_eq!(island.points, vec![(8, 2)]);
+        assert_eq!(island.area(), 0.0);
+    }
+
+    #[test]
+    fn hull_and_simplification() {
+        let image = binary(&[
+            ".......", ".#####.", ".####..", ".###...", ".##....", ".#.....", ".......",
+        ]);
+        let contour = &image.find_contours()[0];
+
+        // a right triangle
+        let mut hull = contour.convex_hull();
+        hull.sort();
+        assert_eq!(hull, vec![(1, 1), (1, 5), (5, 1)]);
+
+        let simplified = contour.simplify(0.5);
+        let mut corners = simplified.points.clone();
+        corners.sort();
+        assert_eq!(corners, vec![(1, 1), (1, 5), (5, 1)]);
+        assert_eq!(simplified.to_svg_path(), "M 1 1 L 1 5 L 5 1 Z");
+    }
+}
@@ -0,0 +1,192 @@
+use super::*;
+
+impl CanvasImage {
+    /// Non-local means denoising (Buades et al.), every pixel becomes the weighted average of
+    /// the pixels in its search window, where the weight depends on how similar the patches
+    /// around the two pixels are rather than on how close they are. Alpha is left untouched.
+    ///
+    /// # Arguments
+    /// * h: filtering strength in [0, 1] intensity units, roughly the noise level to remove, at
+    ///   0 or below the image is returned as it is
+    /// * patch_radius: radius of the square patches that get compared
+    /// * search_radius: radius of the square window similar patches are searched in
+    pub fn non_local_means(&self, h: f64, patch_radius: u32, search_radius: u32) -> CanvasImage {
+        if h <= 0.0 {
+            return CanvasImage::from_vec_with_size(self.data.clone(), self.width, self.height);
+        }
+
+        let planes = Channel::COLOR.map(|channel| self.plane(channel));
+        let s = search_radius as i64;
+
+        let mut sums = Channel::COLOR.map(|_| FloatImage::new(self.width, self.height));
+        let mut weight_sums = FloatImage::new(self.width, self.height);
+
+        // for every offset, compare all pairs of patches that are that far apart at once, the
+        // box mean of the per pixel differences is the patch distance
+        for (dy, dx) in iproduct!(-s..=s, -s..=s) {
+            let difference = FloatImage::from_fn(self.width, self.height, |x, y| {
+                let (sx, sy) = (x as i64 + dx, y as i64 + dy);
+                planes
+                    .iter()
+                    .map(|plane| (plane.get(x, y).unwrap() - plane.reflective(sx, sy)).powi(2))
+                    .sum::<f64>()
+                    / 3.0
+            });
+            let distance = difference.box_mean(patch_radius);
+            let weights = distance.map(|d| (-d / (h * h)).exp());
+
+            for (y, x) in iproduct!(0..self.height, 0..self.width) {
+                let (sx, sy) = (x as i64 + dx, y as i64 + dy);
+                let weight = weights.get(x, y).unwrap();
+
+                for (sum, plane) in sums.iter_mut().zip(planes.iter()) {
+                    let acc = sum.get(x, y).unwrap();
+                    sum.set(x, y, acc + weight * plane.reflective(sx, sy));
+                }
+
+                let acc = weight_sums.get(x, y).unwrap();
+                weight_sums.set(x, y, acc + weight);
+            }
+        }
+
+        let mut denoised =
+            CanvasImage::from_vec_with_size(self.data.clone(), self.width, self.height);
+        for (channel, sum) in Channel::COLOR.iter().zip(sums.iter()) {
+            let plane = sum.zip_map(&weight_sums, |sum, weight| sum / weight);
+            denoised.set_plane(*channel, &plane);
+        }
+
+        denoised
+    }
+
+    /// Perona-Malik anisotropic diffusion, runs a heat equation on the image whose conductivity
+    /// drops to 0 across strong gradients, so noise gets smoothed while edges block the flow.
+    /// Each color channel is diffused on its own, alpha is left untouched.
+    ///
+    /// # Arguments
+    /// * iterations: how many diffusion steps to run
+    /// * kappa: gradient magnitude (in [0, 1] intensity units) above which diffusion mostly
+    ///   stops, the conduction coefficient is `exp(-(|gradient| / kappa)^2)`, at 0 or below
+    ///   nothing flows and the image is returned as it is
+    /// * lambda: step size, must be in (0, 0.25] for the scheme to stay stable
+    pub fn anisotropic_diffusion(&self, iterations: u32, kappa: f64, lambda: f64) -> CanvasImage {
+        assert!(
+            lambda > 0.0 && lambda <= 0.25,
+            "lambda must be in (0, 0.25]"
+        );
+        if kappa <= 0.0 {
+            return CanvasImage::from_vec_with_size(self.data.clone(), self.width, self.height);
+        }
+
+        let conduction = |gradient: f64| (-(gradient / kappa).powi(2)).exp();
+        let mut diffused =
+            CanvasImage::from_vec_with_size(self.data.clone(), self.width, self.height);
+
+        for channel in Channel::COLOR {
+            let mut plane = self.plane(channel);
+
+            for _ in 0..iterations {
+                // clamped reads make the gradient across the border 0, so nothing flows out
+                plane = FloatImage::from_fn(self.width, self.height, |x, y| {
+                    let (x, y) = (x as i64, y as i64);
+                    let center = plane.clamped(x, y);
+
+                    let flow: f64 = [(0, -1), (0, 1), (1, 0), (-1, 0)]
+                        .iter()
+                        .map(|(dx, dy)| plane.clamped(x + dx, y + dy) - center)
+                        .map(|gradient| conduction(gradient) * gradient)
+                        .sum();
+
+                    center + lambda * flow
+                });
+            }
+
+            diffused.set_plane(channel, &plane);
+        }
+
+        diffused
+    }
+}
+
+#[wasm_bindgen]
+pub fn non_local_means(
+    image: ImageData,
+    h: f64,
+    patch_radius: u32,
+    search_radius: u32,
+) -> ImageData {
+    let image = CanvasImage::from_image_data(image);
+    image.non_local_means(h, patch_radius, search_radius).into()
+}
+
+#[wasm_bindgen]
+pub fn anisotropic_diffusion(
+    image: ImageData,
+    iterations: u32,
+    kappa: f64,
+    lambda: f64,
+) -> Result<ImageData, String> {
+    if !(lambda > 0.0 && lambda <= 0.25) {
+        return Err(format!("lambda must be in (0, 0.25], got {lambda}"));
+    }
+
+    let image = CanvasImage::from_image_data(image);
+    Ok(image
+        .anisotropic_diffusion(iterations, kappa, lambda)
+        .into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas_image::test_util::noisy_step_edge;
+
+    /// Mean absolute deviation from the noise free step edge, ignoring the two columns next to
+    /// the edge
+    fn noise_level(image: &CanvasImage) -> f64 {
+        let deviations: Vec<_> = iproduct!(0..16, 0..16)
+            .filter(|(_, x)| *x != 7 && *x != 8)
+            .map(|(y, x)| {
+                let expected: u8 = if x < 8 { 60 } else { 200 };
+                image.r(x, y).unwrap().abs_diff(expected) as f64
+            })
+            .collect();
+
+        deviations.iter().sum::<f64>() / deviations.len() as f64
+    }
+
+    fn assert_edge_kept(image: &CanvasImage) {
+        for y in 0..16 {
+            assert!(image.r(7, y).unwrap() < 100);
+            assert!(image.r(8, y).unwrap() > 160);
+        }
+    }
+
+    #[test]
+    fn non_local_means_removes_noise() {
+        let image = noisy_step_edge(16, 10);
+        let denoised = image.non_local_means(0.1, 1, 3);
+
+        assert!(noise_level(&denoised) < noise_level(&image) / 2.0);
+        assert_edge_kept(&denoised);
+    }
+
+    #[test]
+    fn anisotropic_diffusion_removes_noise() {
+        let image = noisy_step_edge(16, 10);
+        let denoised = image.anisotropic_diffusion(20, 0.1, 0.2);
+
+        assert!(noise_level(&denoised) < noise_level(&image) / 2.0);
+        assert_edge_kept(&denoised);
+    }
+
+    #[test]
+    fn zero_strength_changes_nothing() {
+        let image = noisy_step_edge(16, 10);
+
+        let denoised = image.non_local_means(0.0, 1, 3);
+        assert_eq!(denoised.rgba_slice(), image.rgba_slice());
+        let diffused = image.anisotropic_diffusion(5, 0.0, 0.2);
+        assert_eq!(diffused.rgba_slice(), image.rgba_slice());
+    }
+}
@@ -0,0 +1,226 @@
+use super::*;
+use crate::distance::{Distance, DistanceMetric};
+
+/// Stands in for infinity in the squared distances of the Euclidean transform, the parabola
+/// intersections turn into NaN with a real infinity
+const FAR: f64 = 1e20;
+
+/// Two pass chamfer distance transform, exact for metrics where the distance to any pixel is the
+/// sum of the steps to it from neighbour to neighbour, like Manhattan and Chebyshev. The step
+/// costs come from `metric`.
+fn chamfer(
+    foreground: &[bool],
+    width: u32,
+    height: u32,
+    metric: &dyn DistanceMetric,
+) -> FloatImage {
+    // the neighbours that are already final when scanning from the top left
+    let forward = [(-1, -1), (0, -1), (1, -1), (-1, 0)];
+    let backward = forward.map(|(dx, dy)| (-dx, -dy));
+    let cost = |(dx, dy): (i64, i64)| metric.distance(1, 1, (1 + dx) as u32, (1 + dy) as u32);
+
+    let mut distances = FloatImage::from_vec_with_size(
+        foreground
+            .iter()
+            .map(|f| if *f { 0.0 } else { f64::INFINITY })
+            .collect(),
+        width,
+        height,
+    );
+
+    let mut pass = |pixels: &mut dyn Iterator<Item = (u32, u32)>, mask: [(i64, i64); 4]| {
+        for (x, y) in pixels {
+            let shortest = mask
+                .iter()
+                .map(|(dx, dy)| (x as i64 + dx, y as i64 + dy, cost((*dx, *dy))))
+                .filter(|(nx, ny, _)| {
+                    (0..width as i64).contains(nx) && (0..height as i64).contains(ny)
+                })
+                .map(|(nx, ny, cost)| distances.get(nx as u32, ny as u32).unwrap() + cost)
+                .fold(distances.get(x, y).unwrap(), f64::min);
+
+            distances.set(x, y, shortest);
+        }
+    };
+
+    pass(
+        &mut iproduct!(0..height, 0..width).map(|(y, x)| (x, y)),
+        forward,
+    );
+    pass(
+        &mut iproduct!((0..height).rev(), (0..width).rev()).map(|(y, x)| (x, y)),
+        backward,
+    );
+
+    distances
+}
+
+/// Exact 1D squared Euclidean distance transform of Felzenszwalb and Huttenlocher: the lower
+/// envelope of the parabolas rooted at every sample
+fn squared_distance_1d(f: &[f64]) -> Vec<f64> {
+    let n = f.len();
+    if n == 0 {
+        return Vec::new();
+    }
+
+    // roots of the parabolas in the envelope, and where each one starts being the lowest
+    let mut roots = vec![0usize; n];
+    let mut starts = vec![0f64; n + 1];
+    let mut k = 0;
+    starts[0] = f64::NEG_INFINITY;
+    starts[1] = f64::INFINITY;
+
+    let intersection = |q: usize, p: usize| {
+        ((f[q] + (q * q) as f64) - (f[p] + (p * p) as f64)) / (2.0 * q as f64 - 2.0 * p as f64)
+    };
+
+    for q in 1..n {
+        let mut s = intersection(q, roots[k]);
+        while s <= starts[k] {
+            k -= 1;
+            s = intersection(q, roots[k]);
+        }
+
+        k += 1;
+        roots[k] = q;
+        starts[k] = s;
+        starts[k + 1] = f64::INFINITY;
+    }
+
+    k = 0;
+    (0..n)
+        .map(|q| {
+            while starts[k + 1] < q as f64 {
+                k += 1;
+            }
+            let d = q as f64 - roots[k] as f64;
+            d * d + f[roots[k]]
+        })
+        .collect()
+}
+
+/// Exact Euclidean distance transform, the 1D transform over every column and then every row
+fn euclidean(foreground: &[bool], width: u32, height: u32) -> FloatImage {
+    if width == 0 || height == 0 {
+        return FloatImage::new(width, height);
+    }
+
+    let (w, h) = (width as usize, height as usize);
+    let mut squared: Vec<f64> = foreground
+        .iter()
+        .map(|f| if *f { 0.0 } else { FAR })
+        .collect();
+
+    for x in 0..w {
+        let column: Vec<_> = (0..h).map(|y| squared[y * w + x]).collect();
+        for (y, d) in squared_distance_1d(&column).into_iter().enumerate() {
+            squared[y * w + x] = d;
+        }
+    }
+    for row in squared.chunks_exact_mut(w) {
+        let transformed = squared_distance_1d(row);
+        row.copy_from_slice(&transformed);
+    }
+
+    let distances = squared
+        .into_iter()
+        .map(|d| if d >= FAR { f64::INFINITY } else { d.sqrt() })
+        .collect();
+    FloatImage::from_vec_with_size(distances, width, height)
+}
+
+impl CanvasImage {
+    /// Distance from every pixel to the closest foreground pixel, treating the image as binary.
+    /// Foreground pixels are at distance 0, every pixel is infinitely far if there is no
+    /// foreground at all.
+    ///
+    /// Manhattan and Chebyshev use a two pass chamfer transform, Euclidean the exact linear time
+    /// algorithm of Felzenszwalb and Huttenlocher.
+    pub fn distance_transform(&self, metric: Distance) -> FloatImage {
+        let foreground = self.foreground();
+
+        match metric {
+            Distance::Euclidean => euclidean(&foreground, self.width, self.height),
+            _ => chamfer(&foreground, self.width, self.height, metric.metric()),
+        }
+    }
+}
+
+/// The distance transform scaled so the furthest pixel is white
+#[wasm_bindgen]
+pub fn distance_transform(image: ImageData, metric: Distance) -> ImageData {
+    let image = CanvasImage::from_image_data(image);
+    image
+        .distance_transform(metric)
+        .map(|d| if d.is_finite() { d } else { 0.0 })
+        .normalized()
+        .to_greyscale()
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn distances_to_a_single_pixel() {
+        let mut mask = vec![false; 49];
+        mask[3 * 7 + 3] = true;
+        let image = CanvasImage::from_foreground(mask, 7, 7);
+
+        let corner = |metric| image.distance_transform(metric).get(0, 0).unwrap();
+        assert_eq!(corner(Distance::Manhattan), 6.0);
+        assert_eq!(corner(Distance::Chebyshev), 3.0);
+        assert_eq!(corner(Distance::Euclidean), 18f64.sqrt());
+
+        let empty = CanvasImage::from_foreground(vec![false; 4], 2, 2);
+        assert_eq!(
+            empty.distance_transform(Distance::Euclidean).get(1, 1),
+            Some(f64::INFINITY)
+        );
+    }
+
+    #[test]
+    fn empty_images() {
+        assert!(squared_distance_1d(&[]).is_empty());
+
+        for (width, height) in [(0, 0), (0, 3), (3, 0)] {
+            let image = CanvasImage::from_foreground(Vec::new(), width, height);
+            for metric in [
+                Distance::Manhattan,
+                Distance::Chebyshev,
+                Distance::Euclidean,
+            ] {
+                assert!(image.distance_transform(metric).data().is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(40);
+        let (width, height) = (13, 9);
+        let mask: Vec<bool> = (0..width * height).map(|_| rng.gen_bool(0.08)).collect();
+        let image = CanvasImage::from_foreground(mask.clone(), width, height);
+
+        for metric in [
+            Distance::Manhattan,
+            Distance::Chebyshev,
+            Distance::Euclidean,
+        ] {
+            let transform = image.distance_transform(metric);
+
+            for (y, x) in iproduct!(0..height, 0..width) {
+                let expected = iproduct!(0..height, 0..width)
+                    .filter(|(fy, fx)| mask[(fy * width + fx) as usize])
+                    .map(|(fy, fx)| metric.metric().distance(x, y, fx, fy))
+                    .fold(f64::INFINITY, f64::min);
+
+                let actual = transform.get(x, y).unwrap();
+                assert!((actual - expected).abs() < 1e-9, "{metric:?} at ({x}, {y})");
+            }
+        }
+    }
+}
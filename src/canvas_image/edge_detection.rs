@@ -1,13 +1,17 @@
 use crate::canvas_image::CanvasImage;
+use crate::color_space::hsv_to_rgb;
 use crate::convolution::{BorderStrategy, Kernel};
-use crate::image_index::reflective_indexed;
+use crate::float_image::FloatImage;
 use enum_iterator::Sequence;
 use itertools::{iproduct, izip};
-use num_traits::abs_sub;
 use rand::distributions::{Bernoulli, Distribution};
 use wasm_bindgen::prelude::*;
 use web_sys::ImageData;
 
+/// Sigma of the Laplacian of Gaussian when none is given, about what the classic 5x5 kernel
+/// approximates
+const DEFAULT_LOG_SIGMA: f64 = 1.4;
+
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Sequence)]
 enum Neighbour {
     TopLeft,
@@ -20,7 +24,8 @@ enum Neighbour {
     BottomRight,
 }
 
-/// Iterator that returns pairs of opposing neighbours
+/// Iterator over the pairs of opposing neighbours, every one of the eight neighbours shows up in
+/// exactly one of the four pairs
 struct EightNeighbourIterator {
     x: i32,
     y: i32,
@@ -62,90 +67,147 @@ impl Iterator for EightNeighbourIterator {
             Neighbour::BottomRight => ((cur_x + 1, cur_y + 1), (cur_x - 1, cur_y - 1)),
         };
 
-        // if we are at the bottom right, we are done
+        // the directions after left are the same pairs the other way around
         match self.current.next() {
-            Some(next_dir) => {
+            Some(next_dir) if self.current != Neighbour::Left => {
                 self.current = next_dir;
             }
-            None => self.done = true,
+            _ => self.done = true,
         }
 
         Some(((x, y), (ops_x, ops_y)))
     }
 }
 
-impl CanvasImage {
-    /// ONLY for greyscale
-    /// Assuming the image has already gone through the Laplacian matrix, now we just need to find
-    /// all the points where the value is close to 0.
-    /// # Arguments
-    /// * threshold: the tolerance for how close the value needs to be to 0, calculated via
-    ///                  `abs(value) > threshold`
-    /// # Returns
-    ///  a new image where the edges are white and the rest is black
-    fn greyscale_laplacian_edges(
-        convolved_image: &Vec<f64>,
-        width: u32,
-        height: u32,
-        threshold: f64,
-    ) -> CanvasImage {
-        // access the entire rgba pixel of the convolution image
-        let pixel_access = |x, y| {
-            let x = x as usize;
-            let y = y as usize;
-            let width = width as usize;
-            let index = y * width + x;
-
-            let rgba = convolved_image
-                .as_slice()
-                .chunks_exact(4)
-                .nth(index)
-                .unwrap();
-
-            Some([rgba[0], rgba[1], rgba[2], rgba[3]])
+/// The discrete derivative used to estimate the gradient of an image
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GradientOperator {
+    Sobel,
+    Prewitt,
+    /// Like Sobel, but with weights tuned for better rotational symmetry
+    Scharr,
+    /// 2 x 2 diagonal differences, cheap and sharp but very sensitive to noise
+    Roberts,
+}
+
+/// How the two components of the gradient are combined into its magnitude
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GradientNorm {
+    /// |dx| + |dy|
+    L1,
+    /// sqrt(dx^2 + dy^2)
+    L2,
+}
+
+impl GradientOperator {
+    /// The pair of kernels of the operator, as written they respond to intensity increasing to
+    /// the right and downwards. Roberts is the exception, its kernels respond along the two
+    /// diagonals.
+    fn kernels(&self) -> (Kernel, Kernel) {
+        match self {
+            GradientOperator::Sobel => (
+                Kernel::from_vec(vec![-1.0, 0.0, 1.0, -2.0, 0.0, 2.0, -1.0, 0.0, 1.0], 3, 3),
+                Kernel::from_vec(vec![-1.0, -2.0, -1.0, 0.0, 0.0, 0.0, 1.0, 2.0, 1.0], 3, 3),
+            ),
+            GradientOperator::Prewitt => (
+                Kernel::from_vec(vec![-1.0, 0.0, 1.0, -1.0, 0.0, 1.0, -1.0, 0.0, 1.0], 3, 3),
+                Kernel::from_vec(vec![-1.0, -1.0, -1.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0], 3, 3),
+            ),
+            GradientOperator::Scharr => (
+                Kernel::from_vec(vec![-3.0, 0.0, 3.0, -10.0, 0.0, 10.0, -3.0, 0.0, 3.0], 3, 3),
+                Kernel::from_vec(vec![-3.0, -10.0, -3.0, 0.0, 0.0, 0.0, 3.0, 10.0, 3.0], 3, 3),
+            ),
+            GradientOperator::Roberts => (
+                Kernel::from_vec(vec![-1.0, 0.0, 0.0, 1.0], 2, 2),
+                Kernel::from_vec(vec![0.0, 1.0, -1.0, 0.0], 2, 2),
+            ),
+        }
+    }
+
+    /// What the kernels have to be divided by so a hard step from 0 to 1 has a magnitude of 1
+    fn normalization(&self) -> f64 {
+        match self {
+            GradientOperator::Sobel => 4.0,
+            GradientOperator::Prewitt => 3.0,
+            GradientOperator::Scharr => 16.0,
+            GradientOperator::Roberts => 1.0,
+        }
+    }
+}
+
+/// Gradient of a single channel image, split into its magnitude and its direction
+pub struct Gradient {
+    /// Scaled so a hard step from 0 to 1 has a magnitude of 1
+    pub magnitude: FloatImage,
+    /// Angle of the gradient in radians, in (-pi, pi], 0 points to the right and since y points
+    /// down, pi / 2 points downwards
+    pub orientation: FloatImage,
+}
+
+impl GradientOperator {
+    /// The horizontal and vertical derivatives of `plane`, scaled so a hard step from 0 to 1
+    /// measures 1
+    pub(crate) fn derivatives(&self, plane: &FloatImage) -> (FloatImage, FloatImage) {
+        let (kernel_a, kernel_b) = self.kernels();
+        let scale = self.normalization();
+        let a = plane.correlate(&kernel_a).map(|v| v / scale);
+        let b = plane.correlate(&kernel_b).map(|v| v / scale);
+
+        match self {
+            // a is the difference along (1, 1) and b along (1, -1), rotate them back onto the axes
+            GradientOperator::Roberts => (
+                a.zip_map(&b, |a, b| (a + b) / 2.0),
+                a.zip_map(&b, |a, b| (a - b) / 2.0),
+            ),
+            _ => (a, b),
+        }
+    }
+}
+
+impl Gradient {
+    pub fn of(plane: &FloatImage, operator: GradientOperator, norm: GradientNorm) -> Gradient {
+        let (del_x, del_y) = operator.derivatives(plane);
+
+        let magnitude = match norm {
+            GradientNorm::L1 => del_x.zip_map(&del_y, |x, y| x.abs() + y.abs()),
+            GradientNorm::L2 => del_x.zip_map(&del_y, f64::hypot),
         };
+        let orientation = del_y.zip_map(&del_x, f64::atan2);
 
-        let pixel_reflect = reflective_indexed::<_, _, i64, _>(&pixel_access, width, height);
-
-        let rgba = iproduct!(0..height, 0..width)
-            .map(|(y, x)| {
-                let neighbours =
-                    EightNeighbourIterator::new(x.try_into().unwrap(), y.try_into().unwrap());
-
-                neighbours
-                    .map(|((x, y), (ops_x, ops_y))| {
-                        let [r, g, b, a] = pixel_reflect(x as i64, y as i64);
-                        let [r_ops, g_ops, b_ops, a_ops] =
-                            pixel_reflect(ops_x as i64, ops_y as i64);
-
-                        // test to see if each channel is an edge
-                        [
-                            r.signum() != r_ops.signum() && abs_sub(r, r_ops) > threshold,
-                            g.signum() != g_ops.signum() && abs_sub(g, g_ops) > threshold,
-                            b.signum() != b_ops.signum() && abs_sub(b, b_ops) > threshold,
-                            a.signum() != a_ops.signum() && abs_sub(a, a_ops) > threshold,
-                        ]
-                    })
-                    // we consider a pixel is an edge as long as one or more direction crosses zero
-                    .reduce(|[r1, g1, b1, a1], [r2, g2, b2, a2]| {
-                        [r1 || r2, g1 || g2, b1 || b2, a1 || a2]
-                    })
-                    .unwrap()
-            })
-            .flat_map(|[r, g, b, _a]| {
-                [
-                    if r { 255 } else { 0 },
-                    if g { 255 } else { 0 },
-                    if b { 255 } else { 0 },
-                    255,
-                ]
-            });
+        Gradient {
+            magnitude,
+            orientation,
+        }
+    }
 
-        let buffer = Vec::from_iter(rgba);
+    /// Visualize the gradient as colors, the hue is the orientation and the brightness is the
+    /// magnitude relative to the strongest gradient in the image
+    pub fn to_color(&self) -> CanvasImage {
+        let (_, strongest) = self.magnitude.min_max();
+        let strongest = if strongest > 0.0 { strongest } else { 1.0 };
 
-        CanvasImage::from_vec_with_size(buffer, width, height)
+        let rgba = Iterator::zip(self.magnitude.data().iter(), self.orientation.data().iter())
+            .flat_map(|(magnitude, orientation)| {
+                let hue = orientation.to_degrees().rem_euclid(360.0);
+                let (r, g, b) = hsv_to_rgb(hue, 1.0, magnitude / strongest);
+
+                let [r, g, b] = [r, g, b].map(|v| (v * 255.0).round().clamp(0.0, 255.0) as u8);
+
+                [r, g, b, 255]
+            })
+            .collect();
+
+        CanvasImage::from_vec_with_size(
+            rgba,
+            self.magnitude.horizontal_size(),
+            self.magnitude.vertical_size(),
+        )
     }
+}
 
+impl CanvasImage {
     /// Assuming the image has already gone through a type of gradient kernel, with x direction in `del_x` and y
     /// direction in `del_y`, now just mark the edge by if the magnitude of the gradient is greater than the
     /// `threshold`.
@@ -189,38 +251,42 @@ impl CanvasImage {
         CanvasImage::from_vec_with_size(buffer, width, height)
     }
 
-    pub fn laplacian_edge(&self, threshold: f64) -> CanvasImage {
+    /// Mark the zero crossings of the Laplacian of the luminance
+    ///
+    /// # Arguments
+    /// * threshold: how steep the crossing has to be, the difference between the Laplacian on
+    ///   either side of the pixel must be above it
+    /// * output: whether to mark the edges in white or to show how steep each crossing is
+    pub fn laplacian_edge(&self, threshold: f64, output: EdgeOutput) -> CanvasImage {
         let kernel = Kernel::from_vec(vec![1.0, 1.0, 1.0, 1.0, -8.0, 1.0, 1.0, 1.0, 1.0], 3, 3);
 
-        let convolved = self.convolve(&kernel, BorderStrategy::Reflective);
-
-        let edge_map =
-            CanvasImage::greyscale_laplacian_edges(&convolved, self.width, self.height, threshold);
-        edge_map
+        let laplacian = self.luminance().convolve(&kernel);
+        zero_crossings(&laplacian, threshold).render(output)
     }
 
-    pub fn laplacian_of_gaussian_edge(&self, threshold: f64) -> CanvasImage {
-        let kernel = Kernel::from_vec(
-            vec![
-                0.0, 0.0, -01.0, 0.0, 0.0, 0.0, -1.0, -02.0, -1.0, 0.0, -1.0, -2.0, 16.0, -2.0,
-                -1.0, 0.0, -1.0, -02.0, -1.0, 0.0, 0.0, 0.0, -01.0, 0.0, 0.0,
-            ],
-            5,
-            5,
-        );
-
-        let convolved = self.convolve(&kernel, BorderStrategy::Reflective);
+    /// Mark the zero crossings of the Laplacian of Gaussian of the luminance, larger `sigma`s
+    /// ignore finer details and noise
+    ///
+    /// # Arguments
+    /// * sigma: standard deviation of the Gaussian
+    /// * threshold: how steep the crossing has to be, the difference between the response on
+    ///   either side of the pixel must be above it
+    /// * output: whether to mark the edges in white or to show how steep each crossing is
+    pub fn laplacian_of_gaussian_edge(
+        &self,
+        sigma: f64,
+        threshold: f64,
+        output: EdgeOutput,
+    ) -> CanvasImage {
+        let kernel = Kernel::laplacian_of_gaussian(sigma);
 
-        let edge_map =
-            CanvasImage::greyscale_laplacian_edges(&convolved, self.width, self.height, threshold);
-        edge_map
+        let laplacian = self.luminance().convolve(&kernel);
+        zero_crossings(&laplacian, threshold).render(output)
     }
 
     // TODO: add noise reduction and edge enhancement
     pub fn sobel_edge(&self, threshold: u32) -> CanvasImage {
-        let kernel_x = Kernel::from_vec(vec![-1.0, 0.0, 1.0, -2.0, 0.0, 2.0, -1.0, 0.0, 1.0], 3, 3);
-
-        let kernel_y = Kernel::from_vec(vec![-1.0, -2.0, -1.0, 0.0, 0.0, 0.0, 1.0, 2.0, 1.0], 3, 3);
+        let (kernel_x, kernel_y) = GradientOperator::Sobel.kernels();
 
         let del_x = self.convolve(&kernel_x, BorderStrategy::Reflective);
         let del_y = self.convolve(&kernel_y, BorderStrategy::Reflective);
@@ -230,9 +296,7 @@ impl CanvasImage {
 
     // TODO: add noise reduction and edge enhancement
     pub fn prewitt_edge(&self, threshold: u32) -> CanvasImage {
-        let kernel_x = Kernel::from_vec(vec![-1.0, 0.0, 1.0, -1.0, 0.0, 1.0, -1.0, 0.0, 1.0], 3, 3);
-
-        let kernel_y = Kernel::from_vec(vec![-1.0, -1.0, -1.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0], 3, 3);
+        let (kernel_x, kernel_y) = GradientOperator::Prewitt.kernels();
 
         let del_x = self.convolve(&kernel_x, BorderStrategy::Reflective);
         let del_y = self.convolve(&kernel_y, BorderStrategy::Reflective);
@@ -240,6 +304,36 @@ impl CanvasImage {
         CanvasImage::gradient_edge_localization(self.width, self.height, &del_x, &del_y, threshold)
     }
 
+    /// Gradient of the luminance of the image
+    pub fn gradient(&self, operator: GradientOperator, norm: GradientNorm) -> Gradient {
+        Gradient::of(&self.luminance(), operator, norm)
+    }
+
+    /// Canny edge detector on the luminance of the image, gives thin, connected edges
+    ///
+    /// The luminance is smoothed with a Gaussian, the Sobel gradient is thinned down to its local
+    /// maxima across the edge (non-maximum suppression) and the result is linked with hysteresis:
+    /// pixels above `high` are edges, and so is every pixel above `low` connected to one of them.
+    ///
+    /// # Arguments
+    /// * sigma: standard deviation of the Gaussian pre-smoothing, 0 skips it
+    /// * low, high: gradient magnitude thresholds, a hard step from black to white has a
+    ///   magnitude of 1
+    /// # Returns
+    ///  a new image where the edges are white and the rest is black
+    pub fn canny(&self, sigma: f64, low: f64, high: f64) -> CanvasImage {
+        let mut luminance = self.luminance();
+        if sigma > 0.0 {
+            luminance = luminance.gaussian_blur(sigma);
+        }
+
+        let gradient = Gradient::of(&luminance, GradientOperator::Sobel, GradientNorm::L2);
+        let thinned = non_maximum_suppression(&gradient.magnitude, &gradient.orientation);
+        let edges = hysteresis(&thinned, low, high);
+
+        CanvasImage::from_foreground(edges, self.width, self.height)
+    }
+
     /// Set noise to the image by performing a bernoulli trial for each pixel with probability p. If the trial succeeds,
     /// `noise` is *set* as the pixel for *all* color channels.
     pub fn set_bernoulli_noise(&mut self, p: f64, noise: u8) -> color_eyre::Result<()> {
@@ -268,20 +362,209 @@ impl CanvasImage {
     }
 }
 
+/// What an edge detector produces
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EdgeOutput {
+    /// Edges are white and everything else is black
+    Binary,
+    /// Edges are as bright as they are strong, relative to the strongest one in the image
+    Strength,
+}
+
+impl FloatImage {
+    /// Render the strength of edges, 0 meaning there is no edge
+    fn render(&self, output: EdgeOutput) -> CanvasImage {
+        match output {
+            EdgeOutput::Binary => self.map(|v| if v > 0.0 { 1.0 } else { 0.0 }).to_greyscale(),
+            EdgeOutput::Strength => self.normalized().to_greyscale(),
+        }
+    }
+}
+
+/// Find where `laplacian` crosses zero, a pixel is on an edge if the values on opposite sides of
+/// it have different signs and differ by more than `threshold`. Every pixel only looks at its
+/// four pairs of opposing neighbours, so this is linear in the size of the image.
+///
+/// # Returns
+///  the largest difference across any crossing at each pixel, 0 where there is none
+fn zero_crossings(laplacian: &FloatImage, threshold: f64) -> FloatImage {
+    let (width, height) = (laplacian.horizontal_size(), laplacian.vertical_size());
+
+    FloatImage::from_fn(width, height, |x, y| {
+        EightNeighbourIterator::new(x as i32, y as i32)
+            .map(|((x, y), (ops_x, ops_y))| {
+                let value = laplacian.reflective(x as i64, y as i64);
+                let opposite = laplacian.reflective(ops_x as i64, ops_y as i64);
+
+                let difference = (value - opposite).abs();
+                if value.signum() != opposite.signum() && difference > threshold {
+                    difference
+                } else {
+                    0.0
+                }
+            })
+            .fold(0.0, f64::max)
+    })
+}
+
+/// Only keep the pixels whose gradient magnitude is a local maximum along the gradient direction,
+/// which is quantized to one of 4 directions
+fn non_maximum_suppression(magnitude: &FloatImage, orientation: &FloatImage) -> FloatImage {
+    let (width, height) = (magnitude.horizontal_size(), magnitude.vertical_size());
+
+    FloatImage::from_fn(width, height, |x, y| {
+        let m = magnitude.get(x, y).unwrap();
+        if m == 0.0 {
+            return 0.0;
+        }
+
+        // only the line the gradient lies on matters, fold the angle into [0, 180)
+        let angle = orientation
+            .get(x, y)
+            .unwrap()
+            .to_degrees()
+            .rem_euclid(180.0);
+
+        let (dx, dy) = match angle {
+            a if !(22.5..157.5).contains(&a) => (1, 0),
+            a if a < 67.5 => (1, 1),
+            a if a < 112.5 => (0, 1),
+            _ => (-1, 1),
+        };
+
+        let (x, y) = (x as i64, y as i64);
+        let before = magnitude.clamped(x - dx, y - dy);
+        let after = magnitude.clamped(x + dx, y + dy);
+
+        // on a plateau only the first pixel along the direction survives
+        if m > before && m >= after {
+            m
+        } else {
+            0.0
+        }
+    })
+}
+
+/// Mark every pixel at or above `high` as an edge, then grow the edges into the 8 connected
+/// pixels that are at or above `low`. Pixels with no magnitude at all are never edges, so a
+/// `low` of 0 doesn't flood the whole image.
+fn hysteresis(magnitude: &FloatImage, low: f64, high: f64) -> Vec<bool> {
+    let (width, height) = (
+        magnitude.horizontal_size() as i64,
+        magnitude.vertical_size() as i64,
+    );
+    let mut edges = vec![false; magnitude.data().len()];
+
+    let mut stack: Vec<_> = iproduct!(0..height, 0..width)
+        .filter(|(y, x)| {
+            let value = magnitude.get(*x as u32, *y as u32).unwrap();
+            value > 0.0 && value >= high
+        })
+        .collect();
+    stack
+        .iter()
+        .for_each(|(y, x)| edges[(y * width + x) as usize] = true);
+
+    while let Some((y, x)) = stack.pop() {
+        for (dy, dx) in iproduct!(-1..=1, -1..=1) {
+            let (nx, ny) = (x + dx, y + dy);
+            if !(0..width).contains(&nx) || !(0..height).contains(&ny) {
+                continue;
+            }
+
+            let index = (ny * width + nx) as usize;
+            let value = magnitude.data()[index];
+            if !edges[index] && value > 0.0 && value >= low {
+                edges[index] = true;
+                stack.push((ny, nx));
+            }
+        }
+    }
+
+    edges
+}
+
+/// Gradient magnitude of the luminance as a greyscale image, a hard step from black to white
+/// shows up as white
+#[wasm_bindgen]
+pub fn gradient_magnitude(
+    image: ImageData,
+    operator: GradientOperator,
+    norm: GradientNorm,
+) -> ImageData {
+    let image = CanvasImage::from_image_data(image);
+    let gradient = image.gradient(operator, norm);
+    gradient.magnitude.to_greyscale().into()
+}
+
+/// Gradient orientation of the luminance as colors, the hue is the angle and the brightness the
+/// magnitude
+#[wasm_bindgen]
+pub fn gradient_orientation(image: ImageData, operator: GradientOperator) -> ImageData {
+    let image = CanvasImage::from_image_data(image);
+    let gradient = image.gradient(operator, GradientNorm::L2);
+    gradient.to_color().into()
+}
+
+#[wasm_bindgen]
+pub fn canny(image: ImageData, sigma: f64, low: f64, high: f64) -> ImageData {
+    let image = CanvasImage::from_image_data(image);
+    let edge_map = image.canny(sigma, low, high);
+    edge_map.into()
+}
+
+/// Laplacian zero crossings as white edges, with `threshold` on the 0 to 255 scale of the
+/// color values, see `laplacian_edge_with` for the other outputs
+/// Laplacian zero crossings as white edges, see `laplacian_edge_with` for the other outputs
+///
+/// # Arguments
+/// * threshold: how steep the crossing has to be, on the 0 to 255 scale of the color values
 #[wasm_bindgen]
 pub fn laplacian_edge(image: ImageData, threshold: f64) -> ImageData {
+    laplacian_edge_with(image, threshold, EdgeOutput::Binary)
+}
+
+/// # Arguments
+/// * threshold: how steep the crossing has to be, on the 0 to 255 scale of the color values
+#[wasm_bindgen]
+pub fn laplacian_edge_with(image: ImageData, threshold: f64, output: EdgeOutput) -> ImageData {
     let image = CanvasImage::from_image_data(image);
-    let edge_map = image.laplacian_edge(threshold);
+    let edge_map = image.laplacian_edge(threshold / 255.0, output);
     edge_map.into()
 }
 
+/// Laplacian of Gaussian zero crossings as white edges with `DEFAULT_LOG_SIGMA`, see
+/// `laplacian_of_gaussian_edge_with` to pick the sigma
+///
+/// # Arguments
+/// * threshold: how steep the crossing has to be, on the 0 to 255 scale of the color values
 #[wasm_bindgen]
 pub fn laplacian_of_gaussian_edge(image: ImageData, threshold: f64) -> ImageData {
     let image = CanvasImage::from_image_data(image);
-    let edge_map = image.laplacian_of_gaussian_edge(threshold);
+    let edge_map =
+        image.laplacian_of_gaussian_edge(DEFAULT_LOG_SIGMA, threshold / 255.0, EdgeOutput::Binary);
     edge_map.into()
 }
 
+/// # Arguments
+/// * threshold: how steep the crossing has to be, on the 0 to 255 scale of the color values
+#[wasm_bindgen]
+pub fn laplacian_of_gaussian_edge_with(
+    image: ImageData,
+    sigma: f64,
+    threshold: f64,
+    output: EdgeOutput,
+) -> Result<ImageData, String> {
+    if sigma <= 0.0 {
+        return Err(format!("sigma must be positive, got {sigma}"));
+    }
+
+    let image = CanvasImage::from_image_data(image);
+    let edge_map = image.laplacian_of_gaussian_edge(sigma, threshold / 255.0, output);
+    Ok(edge_map.into())
+}
+
 #[wasm_bindgen]
 pub fn add_salt(image: ImageData, p: f64) -> Result<ImageData, String> {
     let mut image = CanvasImage::from_image_data(image);
@@ -313,7 +596,8 @@ pub fn sobel_edge(image: ImageData, threshold: f64) -> ImageData {
 #[cfg(test)]
 mod tests {
     use crate::canvas_image::edge_detection::EightNeighbourIterator;
-    use crate::canvas_image::CanvasImage;
+    use crate::canvas_image::test_util::white_square;
+    use crate::canvas_image::{CanvasImage, EdgeOutput, GradientNorm, GradientOperator};
     use image::{ImageBuffer, Rgba};
 
     #[test]
@@ -329,7 +613,7 @@ mod tests {
         let height = image.height();
         let canvas_image = CanvasImage::from_vec_with_size(image.into_raw(), width, height);
 
-        let edge_map = canvas_image.laplacian_edge(300.0);
+        let edge_map = canvas_image.laplacian_edge(1.2, EdgeOutput::Binary);
 
         // convert to back to image and save
         let image: ImageBuffer<Rgba<u8>, &[u8]> = ImageBuffer::from_raw(
@@ -354,7 +638,7 @@ mod tests {
         let height = image.height();
         let canvas_image = CanvasImage::from_vec_with_size(image.into_raw(), width, height);
 
-        let edge_map = canvas_image.laplacian_of_gaussian_edge(500.0);
+        let edge_map = canvas_image.laplacian_of_gaussian_edge(1.4, 0.1, EdgeOutput::Binary);
 
         // convert to back to image and save
         let image: ImageBuffer<Rgba<u8>, &[u8]> = ImageBuffer::from_raw(
@@ -366,11 +650,117 @@ mod tests {
         image.save("laplacian_of_gaussian.png").unwrap();
     }
 
+    #[test]
+    fn every_operator_measures_a_unit_step_as_one() {
+        for operator in [
+            GradientOperator::Sobel,
+            GradientOperator::Prewitt,
+            GradientOperator::Scharr,
+            GradientOperator::Roberts,
+        ] {
+            let gradient = white_square().gradient(operator, GradientNorm::L2);
+            let left = gradient.magnitude.get(5, 10).unwrap();
+            assert!((left - 1.0).abs() < 1e-9, "{:?}", operator);
+
+            // left side of the square points right, top side points down
+            let left = gradient.orientation.get(5, 10).unwrap();
+            let top = gradient.orientation.get(10, 5).unwrap();
+            assert!(left.abs() < 1e-9, "{:?}", operator);
+            assert!(
+                (top - std::f64::consts::FRAC_PI_2).abs() < 1e-9,
+                "{:?}",
+                operator
+            );
+        }
+    }
+
+    #[test]
+    fn orientation_colors() {
+        let colors = white_square()
+            .gradient(GradientOperator::Sobel, GradientNorm::L2)
+            .to_color();
+
+        // pointing right is red, flat areas are black
+        let (r, g, b, _) = colors.rgba(5, 10).unwrap();
+        assert!(r > 200 && g == 0 && b == 0);
+        assert_eq!(colors.rgba(0, 0), Some((0, 0, 0, 255)));
+    }
+
+    #[test]
+    fn canny_finds_thin_closed_outline() {
+        let edges = white_square().canny(1.0, 0.1, 0.3);
+        let foreground = edges.foreground();
+        let is_edge = |x: usize, y: usize| foreground[y * 20 + x];
+
+        // every row crossing the square has exactly one edge pixel on each side
+        for y in 6..14 {
+            let left = (0..10).filter(|x| is_edge(*x, y)).count();
+            let right = (10..20).filter(|x| is_edge(*x, y)).count();
+            assert_eq!((left, right), (1, 1), "row {}", y);
+        }
+
+        // and nothing far away from the border of the square
+        assert!(!is_edge(10, 10));
+        assert!(!is_edge(0, 0));
+    }
+
+    #[test]
+    fn canny_zero_low_threshold_only_follows_edges() {
+        let edges = white_square().canny(1.0, 0.0, 0.3);
+        let foreground = edges.foreground();
+
+        assert!(!foreground[10 * 20 + 10]);
+        assert!(!foreground[0]);
+        assert!(foreground.iter().filter(|edge| **edge).count() < 60);
+    }
+
+    #[test]
+    fn canny_high_threshold_rejects_weak_edges() {
+        let edges = white_square().canny(1.0, 0.1, 2.0);
+        assert!(edges.foreground().iter().all(|edge| !edge));
+    }
+
     #[test]
     fn sanity() {
         let wtf = EightNeighbourIterator::new(0, 0);
         let wtf = wtf.collect::<Vec<_>>();
 
-        print!("{:?}", wtf)
+        print!("{:?}", wtf);
+        assert_eq!(wtf.len(), 4);
+
+        // every neighbour shows up exactly once
+        let mut neighbours: Vec<_> = wtf.iter().flat_map(|(a, b)| [*a, *b]).collect();
+        neighbours.sort();
+        neighbours.dedup();
+        assert_eq!(neighbours.len(), 8);
+    }
+
+    #[test]
+    fn zero_crossings_outline_the_square() {
+        let image = white_square();
+
+        for edges in [
+            image.laplacian_edge(0.5, EdgeOutput::Binary),
+            image.laplacian_of_gaussian_edge(1.0, 0.05, EdgeOutput::Binary),
+        ] {
+            let foreground = edges.foreground();
+            let is_edge = |x: usize, y: usize| foreground[y * 20 + x];
+
+            // the border of the square is found on both sides, nothing in the flat areas
+            assert!(is_edge(4, 10) || is_edge(5, 10));
+            assert!(is_edge(14, 10) || is_edge(15, 10));
+            assert!(!is_edge(10, 10));
+            assert!(!is_edge(0, 0));
+        }
+    }
+
+    #[test]
+    fn zero_crossing_strength() {
+        let strength = white_square().laplacian_of_gaussian_edge(1.0, 0.0, EdgeOutput::Strength);
+
+        // the crossing on the straight part of the border is steeper than the one at the corner
+        let side = strength.r(5, 10).unwrap().max(strength.r(4, 10).unwrap());
+        assert!(side > 0);
+        assert_eq!(strength.r(10, 10), Some(0));
     }
 }
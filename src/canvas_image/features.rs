@@ -0,0 +1,278 @@
+use super::*;
+
+/// Standard deviation of the window `good_features_to_track` sums the structure tensor over
+const SHI_TOMASI_SIGMA: f64 = 1.0;
+
+/// An interesting point of an image, such as a corner
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Keypoint {
+    pub x: f64,
+    pub y: f64,
+    /// How strongly the detector responded, only comparable between keypoints of the same
+    /// detector
+    pub response: f64,
+}
+
+impl Keypoint {
+    pub fn distance(&self, other: &Keypoint) -> f64 {
+        f64::hypot(self.x - other.x, self.y - other.y)
+    }
+}
+
+/// The structure tensor `[[xx, xy], [xy, yy]]` at every pixel
+pub(super) struct StructureTensor {
+    xx: FloatImage,
+    xy: FloatImage,
+    yy: FloatImage,
+}
+
+impl StructureTensor {
+    /// Products of the Sobel derivatives of `plane`, summed over a Gaussian window with
+    /// standard deviation `sigma`
+    pub(super) fn of(plane: &FloatImage, sigma: f64) -> StructureTensor {
+        let (del_x, del_y) = GradientOperator::Sobel.derivatives(plane);
+
+        let window = |image: FloatImage| {
+            if sigma > 0.0 {
+                image.gaussian_blur(sigma)
+            } else {
+                image
+            }
+        };
+
+        StructureTensor {
+            xx: window(del_x.map(|v| v * v)),
+            xy: window(del_x.zip_map(&del_y, |x, y| x * y)),
+            yy: window(del_y.map(|v| v * v)),
+        }
+    }
+
+    /// Harris' corner measure `det - k * trace^2`
+    pub(super) fn harris(&self, k: f64) -> FloatImage {
+        FloatImage::from_fn(
+            self.xx.horizontal_size(),
+            self.xx.vertical_size(),
+            |x, y| {
+                let (xx, xy, yy) = self.at(x, y);
+                xx * yy - xy * xy - k * (xx + yy).powi(2)
+            },
+        )
+    }
+
+    /// The smaller eigenvalue of the tensor, Shi and Tomasi's corner measure
+    fn min_eigenvalue(&self) -> FloatImage {
+        FloatImage::from_fn(
+            self.xx.horizontal_size(),
+            self.xx.vertical_size(),
+            |x, y| {
+                let (xx, xy, yy) = self.at(x, y);
+                (xx + yy) / 2.0 - f64::hypot((xx - yy) / 2.0, xy)
+            },
+        )
+    }
+
+    fn at(&self, x: u32, y: u32) -> (f64, f64, f64) {
+        (
+            self.xx.get(x, y).unwrap(),
+            self.xy.get(x, y).unwrap(),
+            self.yy.get(x, y).unwrap(),
+        )
+    }
+}
+
+/// Every pixel of `response` above `threshold` that is the maximum of its 3 x 3 neighbourhood,
+/// strongest first
+pub(super) fn local_maxima(response: &FloatImage, threshold: f64) -> Vec<Keypoint> {
+    let (width, height) = (response.horizontal_size(), response.vertical_size());
+
+    let mut keypoints: Vec<_> = iproduct!(0..height, 0..width)
+        .filter_map(|(y, x)| {
+            let value = response.get(x, y).unwrap();
+            let is_maximum = iproduct!(-1..=1, -1..=1)
+                .filter(|offset| *offset != (0, 0))
+                .all(|(dy, dx)| value >= response.clamped(x as i64 + dx, y as i64 + dy));
+
+            (value > threshold && is_maximum).then_some(Keypoint {
+                x: x as f64,
+                y: y as f64,
+                response: value,
+            })
+        })
+        .collect();
+
+    keypoints.sort_by(|a, b| b.response.total_cmp(&a.response));
+    keypoints
+}
+
+impl CanvasImage {
+    /// Harris corner detector on the luminance of the image
+    ///
+    /// # Arguments
+    /// * k: sensitivity, usually between 0.04 and 0.06, larger values reject more edges
+    /// * sigma: standard deviation of the Gaussian window the gradients are gathered over
+    /// * threshold: minimum Harris response of a corner, a hard step from black to white has a
+    ///   gradient of 1
+    /// # Returns
+    ///  the corners, strongest first
+    pub fn harris_corners(&self, k: f64, sigma: f64, threshold: f64) -> Vec<Keypoint> {
+        let response = StructureTensor::of(&self.luminance(), sigma).harris(k);
+        local_maxima(&response, threshold)
+    }
+
+    /// Shi-Tomasi corner detector on the luminance of the image, picks the strongest corners
+    /// that are at least `min_distance` apart
+    ///
+    /// # Arguments
+    /// * max_corners: how many corners to return at most, 0 means no limit
+    /// * quality: fraction of the strongest corner's response a corner needs to be kept, in
+    ///   (0, 1]
+    /// * min_distance: minimum euclidean distance between two returned corners
+    /// # Returns
+    ///  the corners, strongest first
+    pub fn good_features_to_track(
+        &self,
+        max_corners: usize,
+        quality: f64,
+        min_distance: f64,
+    ) -> Vec<Keypoint> {
+        let response = StructureTensor::of(&self.luminance(), SHI_TOMASI_SIGMA).min_eigenvalue();
+        let (_, strongest) = response.min_max();
+
+        let max_corners = if max_corners == 0 {
+            usize::MAX
+        } else {
+            max_corners
+        };
+
+        let mut corners: Vec<Keypoint> = Vec::new();
+        for candidate in local_maxima(&response, (quality * strongest).max(0.0)) {
+            if corners.len() == max_corners {
+                break;
+            }
+
+            if corners
+                .iter()
+                .all(|corner| corner.distance(&candidate) >= min_distance)
+            {
+                corners.push(candidate);
+            }
+        }
+
+        corners
+    }
+
+    /// Draw a circle around every keypoint
+    pub fn draw_keypoints(&self, keypoints: &[Keypoint], color: (u8, u8, u8)) -> CanvasImage {
+        const RADIUS: f64 = 3.0;
+
+        let mut image = CanvasImage::from_vec_with_size(self.data.clone(), self.width, self.height);
+        let (r, g, b) = color;
+
+        for keypoint in keypoints {
+            let reach = RADIUS.ceil() as i64 + 1;
+            let (cx, cy) = (keypoint.x.round() as i64, keypoint.y.round() as i64);
+
+            for (y, x) in iproduct!(cy - reach..=cy + reach, cx - reach..=cx + reach) {
+                let outside = x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64;
+                let distance = f64::hypot(x as f64 - keypoint.x, y as f64 - keypoint.y);
+
+                if outside || (distance - RADIUS).abs() > 0.5 {
+                    continue;
+                }
+
+                let index = (y as usize * self.width as usize + x as usize) * 4;
+                image.data[index..index + 4].copy_from_slice(&[r, g, b, 255]);
+            }
+        }
+
+        image
+    }
+}
+
+/// Flatten keypoints into `[x0, y0, x1, y1, ...]`
+pub(super) fn coordinates(keypoints: &[Keypoint]) -> Vec<f64> {
+    keypoints.iter().flat_map(|k| [k.x, k.y]).collect()
+}
+
+/// # Returns
+///  the coordinates of the corners as `[x0, y0, x1, y1, ...]`, strongest first
+#[wasm_bindgen]
+pub fn harris_corners(image: ImageData, k: f64, sigma: f64, threshold: f64) -> Vec<f64> {
+    let image = CanvasImage::from_image_data(image);
+    coordinates(&image.harris_corners(k, sigma, threshold))
+}
+
+/// # Returns
+///  the coordinates of the corners as `[x0, y0, x1, y1, ...]`, strongest first
+#[wasm_bindgen]
+pub fn good_features_to_track(
+    image: ImageData,
+    max_corners: usize,
+    quality: f64,
+    min_distance: f64,
+) -> Vec<f64> {
+    let image = CanvasImage::from_image_data(image);
+    coordinates(&image.good_features_to_track(max_corners, quality, min_distance))
+}
+
+/// Circle the corners, given as `[x0, y0, x1, y1, ...]` like the corner detectors return them
+#[wasm_bindgen]
+pub fn draw_corners(image: ImageData, corners: &[f64], r: u8, g: u8, b: u8) -> ImageData {
+    let image = CanvasImage::from_image_data(image);
+    let keypoints: Vec<_> = corners
+        .chunks_exact(2)
+        .map(|xy| Keypoint {
+            x: xy[0],
+            y: xy[1],
+            response: 0.0,
+        })
+        .collect();
+
+    image.draw_keypoints(&keypoints, (r, g, b)).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas_image::test_util::white_square;
+
+    /// Every keypoint is within 1.5 pixels of one of the corners of the square, and every corner
+    /// has been found
+    fn assert_square_corners(keypoints: &[Keypoint]) {
+        let corners = [(4.5, 4.5), (14.5, 4.5), (4.5, 14.5), (14.5, 14.5)].map(|(x, y)| Keypoint {
+            x,
+            y,
+            response: 0.0,
+        });
+
+        for keypoint in keypoints {
+            assert!(
+                corners.iter().any(|c| c.distance(keypoint) < 1.5),
+                "{keypoint:?} is not a corner"
+            );
+        }
+        for corner in corners {
+            assert!(keypoints.iter().any(|k| k.distance(&corner) < 1.5));
+        }
+    }
+
+    #[test]
+    fn harris_finds_the_four_corners() {
+        let corners = white_square().harris_corners(0.05, 1.0, 0.001);
+
+        assert_eq!(corners.len(), 4);
+        assert_square_corners(&corners);
+    }
+
+    #[test]
+    fn good_features_keeps_them_apart() {
+        let image = white_square();
+
+        let corners = image.good_features_to_track(0, 0.1, 5.0);
+        assert_eq!(corners.len(), 4);
+        assert_square_corners(&corners);
+        assert!(corners.windows(2).all(|w| w[0].response >= w[1].response));
+
+        assert_eq!(image.good_features_to_track(2, 0.1, 5.0).len(), 2);
+    }
+}
@@ -1,6 +1,6 @@
 use super::*;
-use itertools::iproduct;
-use wasm_bindgen::prelude::*;
+use crate::distance::Distance;
+use crate::float_image::reflect;
 
 #[wasm_bindgen]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
@@ -8,66 +8,310 @@ pub enum FilterMode {
     Min,
     Max,
     Median,
+    /// The most common intensity in the neighbourhood
+    Mode,
+}
+
+/// What gets picked out of the intensities in the neighbourhood
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Statistic {
+    /// The value at the given percentile in [0, 100] of the sorted intensities
+    Percentile(f64),
+    /// Mean of the intensities after throwing away the given fraction of the lowest and the
+    /// highest ones
+    AlphaTrimmedMean(f64),
+    Mode,
+}
+
+impl From<FilterMode> for Statistic {
+    fn from(mode: FilterMode) -> Self {
+        match mode {
+            FilterMode::Min => Statistic::Percentile(0.0),
+            FilterMode::Max => Statistic::Percentile(100.0),
+            FilterMode::Median => Statistic::Percentile(50.0),
+            FilterMode::Mode => Statistic::Mode,
+        }
+    }
+}
+
+/// Histogram of the intensities currently inside the window
+struct WindowHistogram {
+    bins: [u32; 256],
+    count: u32,
+}
+
+impl WindowHistogram {
+    fn new() -> Self {
+        WindowHistogram {
+            bins: [0; 256],
+            count: 0,
+        }
+    }
+
+    fn add(&mut self, intensity: u8) {
+        self.bins[intensity as usize] += 1;
+        self.count += 1;
+    }
+
+    fn remove(&mut self, intensity: u8) {
+        self.bins[intensity as usize] -= 1;
+        self.count -= 1;
+    }
+
+    fn add_histogram(&mut self, other: &WindowHistogram) {
+        self.bins
+            .iter_mut()
+            .zip(other.bins.iter())
+            .for_each(|(a, b)| *a += b);
+        self.count += other.count;
+    }
+
+    fn remove_histogram(&mut self, other: &WindowHistogram) {
+        self.bins
+            .iter_mut()
+            .zip(other.bins.iter())
+            .for_each(|(a, b)| *a -= b);
+        self.count -= other.count;
+    }
+
+    /// The intensity at the given 0 based position of the sorted window
+    fn nth(&self, n: u32) -> u8 {
+        let mut seen = 0;
+        for (intensity, bin) in self.bins.iter().enumerate() {
+            seen += bin;
+            if seen > n {
+                return intensity as u8;
+            }
+        }
+
+        u8::MAX
+    }
+
+    fn evaluate(&self, statistic: Statistic) -> u8 {
+        match statistic {
+            Statistic::Percentile(percentile) => {
+                let n = (percentile / 100.0 * (self.count - 1) as f64).round() as u32;
+                self.nth(n)
+            }
+            Statistic::AlphaTrimmedMean(trim) => {
+                let trimmed = (trim * self.count as f64).floor() as u32;
+                let (low, high) = (trimmed, self.count - trimmed);
+
+                // walk the bins and only sum up the part of each bin that is in [low, high)
+                let mut seen = 0;
+                let mut sum = 0f64;
+                for (intensity, bin) in self.bins.iter().enumerate() {
+                    let start = seen.max(low);
+                    let end = (seen + bin).min(high);
+                    if end > start {
+                        sum += (end - start) as f64 * intensity as f64;
+                    }
+                    seen += bin;
+                }
+
+                (sum / (high - low) as f64).round() as u8
+            }
+            Statistic::Mode => {
+                // ties go to the darker intensity
+                let (intensity, _) = self
+                    .bins
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .max_by_key(|(_, bin)| **bin)
+                    .unwrap();
+                intensity as u8
+            }
+        }
+    }
+}
+
+/// For each row offset in [-distance, distance], how far the window extends to the left and the
+/// right of the center. Every shape is symmetric and convex along a row, so this is all we need
+/// to slide it around.
+fn row_spans(distance: u32, shape: Distance) -> Vec<(i64, i64)> {
+    let metric = shape.metric();
+    let r = distance as i64;
+
+    (-r..=r)
+        .map(|dy| {
+            let half_width = (0..=distance)
+                .take_while(|dx| {
+                    metric.distance(0, 0, *dx, dy.unsigned_abs() as u32) <= distance as f64
+                })
+                .last()
+                .unwrap_or(0) as i64;
+
+            (dy, half_width)
+        })
+        .collect()
 }
 
 impl CanvasImage {
-    /// Filters the image using the given filter and distance
+    /// Filters the image using the given filter and distance, the neighbourhood is every pixel
+    /// within `distance` in Manhattan distance, i.e. a diamond. That costs O(distance) per pixel,
+    /// the square window of `filter_with_shape` with `Distance::Chebyshev` runs in constant time.
     pub fn filter(&self, filter: FilterMode, distance: u32) -> CanvasImage {
-        // Given an iterator over the pixels within the distance, returns the filtered value
-        let strategy: Box<dyn Fn(&mut dyn Iterator<Item = u8>) -> Option<u8>> = match filter {
-            FilterMode::Min => Box::new(|iter| iter.min()),
-            FilterMode::Max => Box::new(|iter| iter.max()),
-            FilterMode::Median => Box::new(|iter| {
-                let mut vec: Vec<_> = iter.collect();
-                vec.sort_unstable();
-                vec.get(vec.len() / 2).copied()
-            }),
-        };
-
-        let distance = distance as isize;
-        let r = |x, y| ReflectiveIndexedImage::g(self, x, y);
-        let g = |x, y| ReflectiveIndexedImage::g(self, x, y);
-        let b = |x, y| ReflectiveIndexedImage::g(self, x, y);
-        let a = |x, y| ReflectiveIndexedImage::g(self, x, y);
-
-        let width = self.horizontal_size() as isize;
-        let height = self.vertical_size() as isize;
-
-        // create the set that contains all x and y offsets that are within the distance
-        let offsets = iproduct!(-distance..=distance).flat_map(|x_offset| {
-            iproduct!(-(distance - x_offset.abs())..=(distance - x_offset.abs()))
-                .map(move |y_offset| (x_offset, y_offset))
-        });
-
-        let rgba = iproduct!(0..height, 0..width)
-            // map each pixel to a set of pixels that are within the distance
-            .map(|(y, x)| {
-                let mut r = offsets
-                    .clone()
-                    .map(|(y_offset, x_offset)| (y + y_offset, x + x_offset))
-                    .map(|(y_prime, x_prime)| r(x_prime as i32, y_prime as i32));
-                let r = strategy(&mut r);
-
-                let mut g = offsets
-                    .clone()
-                    .map(|(y_offset, x_offset)| (y + y_offset, x + x_offset))
-                    .map(|(y_prime, x_prime)| g(x_prime as i32, y_prime as i32));
-                let g = strategy(&mut g);
-
-                let mut b = offsets
-                    .clone()
-                    .map(|(y_offset, x_offset)| (y + y_offset, x + x_offset))
-                    .map(|(y_prime, x_prime)| b(x_prime as i32, y_prime as i32));
-                let b = strategy(&mut b);
-
-                let a = a(x as i32, y as i32);
-
-                [r.unwrap(), g.unwrap(), b.unwrap(), a]
-            });
-
-        let buffer = Vec::from_iter(rgba.flatten());
-
-        CanvasImage::from_vec_with_size(buffer, self.horizontal_size(), self.vertical_size())
+        self.filter_with_shape(filter, distance, Distance::Manhattan)
+    }
+
+    /// Filters the image using the given filter, the neighbourhood is every pixel within
+    /// `distance` under the metric of `shape`: Manhattan gives a diamond, Chebyshev a square and
+    /// Euclidean a disk. Only the square runs in constant time per pixel, the other shapes slide
+    /// a histogram along every row of the window, which costs O(distance) per pixel.
+    pub fn filter_with_shape(
+        &self,
+        filter: FilterMode,
+        distance: u32,
+        shape: Distance,
+    ) -> CanvasImage {
+        self.rank_filter(filter.into(), distance, shape)
+    }
+
+    /// Replace every pixel by the given percentile of its neighbourhood, 0 is a min filter, 50 a
+    /// median filter and 100 a max filter
+    pub fn percentile_filter(
+        &self,
+        percentile: f64,
+        distance: u32,
+        shape: Distance,
+    ) -> CanvasImage {
+        assert!(
+            (0.0..=100.0).contains(&percentile),
+            "percentile must be in [0, 100]"
+        );
+        self.rank_filter(Statistic::Percentile(percentile), distance, shape)
+    }
+
+    /// Replace every pixel by the mean of its neighbourhood after throwing away the `trim`
+    /// fraction of the darkest and of the brightest values. 0 is a mean filter and anything close
+    /// to 0.5 is a median filter, in between it deals with both salt and pepper and Gaussian
+    /// noise.
+    pub fn alpha_trimmed_mean_filter(
+        &self,
+        trim: f64,
+        distance: u32,
+        shape: Distance,
+    ) -> CanvasImage {
+        assert!((0.0..0.5).contains(&trim), "trim must be in [0, 0.5)");
+        self.rank_filter(Statistic::AlphaTrimmedMean(trim), distance, shape)
+    }
+
+    /// Replace every pixel by the most common value in its neighbourhood
+    pub fn mode_filter(&self, distance: u32, shape: Distance) -> CanvasImage {
+        self.rank_filter(Statistic::Mode, distance, shape)
+    }
+
+    /// Every color channel is filtered on its own, alpha is left untouched
+    fn rank_filter(&self, statistic: Statistic, distance: u32, shape: Distance) -> CanvasImage {
+        let mut filtered =
+            CanvasImage::from_vec_with_size(self.data.clone(), self.width, self.height);
+
+        for channel in Channel::COLOR {
+            let intensities: Vec<_> = self
+                .data
+                .chunks_exact(4)
+                .map(|pixel| pixel[channel.offset()])
+                .collect();
+
+            let result = match shape {
+                Distance::Chebyshev => self.square_window_filter(&intensities, statistic, distance),
+                _ => {
+                    self.sliding_window_filter(&intensities, statistic, &row_spans(distance, shape))
+                }
+            };
+
+            filtered
+                .data
+                .chunks_exact_mut(4)
+                .zip(result)
+                .for_each(|(pixel, v)| pixel[channel.offset()] = v);
+        }
+
+        filtered
+    }
+
+    /// Huang's sliding histogram, moving the window one pixel to the right only removes the
+    /// leftmost pixel and adds one on the right for every row of the window, so the cost per
+    /// pixel grows linearly with the radius instead of quadratically
+    fn sliding_window_filter(
+        &self,
+        intensities: &[u8],
+        statistic: Statistic,
+        spans: &[(i64, i64)],
+    ) -> Vec<u8> {
+        let (width, height) = (self.width as i64, self.height as i64);
+        let at =
+            |x: i64, y: i64| intensities[(reflect(y, height) * width + reflect(x, width)) as usize];
+
+        let mut result = Vec::with_capacity(intensities.len());
+        for y in 0..height {
+            let mut histogram = WindowHistogram::new();
+            for (dy, half_width) in spans {
+                for dx in -half_width..=*half_width {
+                    histogram.add(at(dx, y + dy));
+                }
+            }
+            result.push(histogram.evaluate(statistic));
+
+            for x in 1..width {
+                for (dy, half_width) in spans {
+                    histogram.remove(at(x - 1 - half_width, y + dy));
+                    histogram.add(at(x + half_width, y + dy));
+                }
+                result.push(histogram.evaluate(statistic));
+            }
+        }
+
+        result
+    }
+
+    /// Perreault and Hébert's constant time filter for square windows, keeps one histogram per
+    /// column that slides down with the window, the window histogram is then updated by
+    /// subtracting the column that leaves and adding the one that enters, neither of which
+    /// depends on the radius
+    fn square_window_filter(
+        &self,
+        intensities: &[u8],
+        statistic: Statistic,
+        distance: u32,
+    ) -> Vec<u8> {
+        let (width, height) = (self.width as i64, self.height as i64);
+        let r = distance as i64;
+        let at = |x: i64, y: i64| intensities[(reflect(y, height) * width + x) as usize];
+
+        // column histograms for the rows [-r, r] of the first window
+        let mut columns: Vec<_> = (0..width)
+            .map(|x| {
+                let mut histogram = WindowHistogram::new();
+                (-r..=r).for_each(|y| histogram.add(at(x, y)));
+                histogram
+            })
+            .collect();
+
+        let mut result = Vec::with_capacity(intensities.len());
+        for y in 0..height {
+            if y > 0 {
+                for (x, column) in columns.iter_mut().enumerate() {
+                    column.remove(at(x as i64, y - r - 1));
+                    column.add(at(x as i64, y + r));
+                }
+            }
+
+            let column = |x: i64| &columns[reflect(x, width) as usize];
+
+            let mut histogram = WindowHistogram::new();
+            (-r..=r).for_each(|x| histogram.add_histogram(column(x)));
+            result.push(histogram.evaluate(statistic));
+
+            for x in 1..width {
+                histogram.remove_histogram(column(x - r - 1));
+                histogram.add_histogram(column(x + r));
+                result.push(histogram.evaluate(statistic));
+            }
+        }
+
+        result
     }
 }
 
@@ -78,10 +322,48 @@ pub fn filter(image: ImageData, distance: u32, filter: FilterMode) -> ImageData
     filtered.into()
 }
 
+#[wasm_bindgen]
+pub fn filter_with_shape(
+    image: ImageData,
+    distance: u32,
+    filter: FilterMode,
+    shape: Distance,
+) -> ImageData {
+    let canvas_image = CanvasImage::from_image_data(image);
+    let filtered = canvas_image.filter_with_shape(filter, distance, shape);
+    filtered.into()
+}
+
+#[wasm_bindgen]
+pub fn percentile_filter(
+    image: ImageData,
+    percentile: f64,
+    distance: u32,
+    shape: Distance,
+) -> ImageData {
+    let canvas_image = CanvasImage::from_image_data(image);
+    let filtered = canvas_image.percentile_filter(percentile, distance, shape);
+    filtered.into()
+}
+
+#[wasm_bindgen]
+pub fn alpha_trimmed_mean_filter(
+    image: ImageData,
+    trim: f64,
+    distance: u32,
+    shape: Distance,
+) -> ImageData {
+    let canvas_image = CanvasImage::from_image_data(image);
+    let filtered = canvas_image.alpha_trimmed_mean_filter(trim, distance, shape);
+    filtered.into()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use image::{ImageBuffer, Rgba};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
 
     #[test]
     fn min_filter() {
@@ -108,4 +390,113 @@ mod tests {
         .unwrap();
         image.save("meme_min_filter.png").unwrap();
     }
+
+    fn random_image(width: u32, height: u32) -> CanvasImage {
+        let mut rng = StdRng::seed_from_u64(7);
+        let rgba = (0..width * height * 4).map(|_| rng.gen());
+        CanvasImage::from_vec_with_size(rgba.collect(), width, height)
+    }
+
+    /// Sort the whole neighbourhood of every pixel, slow but obviously correct
+    fn brute_force(
+        image: &CanvasImage,
+        percentile: f64,
+        distance: u32,
+        shape: Distance,
+    ) -> Vec<u8> {
+        let metric = shape.metric();
+        let (width, height) = (image.horizontal_size() as i64, image.vertical_size() as i64);
+        let r = distance as i64;
+
+        iproduct!(0..height, 0..width)
+            .flat_map(|(y, x)| {
+                let mut pixel = [0u8; 4];
+                for channel in [Channel::Red, Channel::Green, Channel::Blue, Channel::Alpha] {
+                    let offset = channel.offset();
+
+                    if channel == Channel::Alpha {
+                        pixel[offset] = image.rgba_slice()[((y * width + x) * 4) as usize + offset];
+                        continue;
+                    }
+
+                    let mut window: Vec<_> = iproduct!(-r..=r, -r..=r)
+                        .filter(|(dy, dx)| {
+                            metric.distance(
+                                0,
+                                0,
+                                dx.unsigned_abs() as u32,
+                                dy.unsigned_abs() as u32,
+                            ) <= distance as f64
+                        })
+                        .map(|(dy, dx)| {
+                            let (x, y) = (reflect(x + dx, width), reflect(y + dy, height));
+                            image.rgba_slice()[((y * width + x) * 4) as usize + offset]
+                        })
+                        .collect();
+                    window.sort_unstable();
+
+                    let n = (percentile / 100.0 * (window.len() - 1) as f64).round() as usize;
+                    pixel[offset] = window[n];
+                }
+                pixel
+            })
+            .collect()
+    }
+
+    #[test]
+    fn every_channel_is_filtered_on_its_own() {
+        // a single bright pixel that is only red, its neighbours are only green
+        #[rustfmt::skip]
+        let image = CanvasImage::from_vec_with_size(vec![
+            0, 200, 0, 10,   0, 200, 0, 20,   0, 200, 0, 30,
+            0, 200, 0, 40, 255,   0, 0, 50,   0, 200, 0, 60,
+            0, 200, 0, 70,   0, 200, 0, 80,   0, 200, 0, 90,
+        ], 3, 3);
+
+        let filtered = image.filter(FilterMode::Max, 1);
+        assert_eq!(filtered.rgba(1, 1), Some((255, 200, 0, 50)));
+        assert_eq!(filtered.rgba(0, 0), Some((0, 200, 0, 10)));
+
+        let filtered = image.filter(FilterMode::Min, 1);
+        assert_eq!(filtered.rgba(1, 1), Some((0, 0, 0, 50)));
+        assert_eq!(filtered.rgba(2, 2), Some((0, 200, 0, 90)));
+    }
+
+    #[test]
+    fn sliding_histograms_match_sorting() {
+        let image = random_image(13, 9);
+
+        for (shape, percentile) in iproduct!(
+            [
+                Distance::Manhattan,
+                Distance::Chebyshev,
+                Distance::Euclidean
+            ],
+            [0.0, 30.0, 50.0, 100.0]
+        ) {
+            let filtered = image.percentile_filter(percentile, 3, shape);
+            let expected = brute_force(&image, percentile, 3, shape);
+
+            assert_eq!(
+                filtered.rgba_slice(),
+                expected.as_slice(),
+                "{:?} {}",
+                shape,
+                percentile
+            );
+        }
+    }
+
+    #[test]
+    fn alpha_trimmed_mean_and_mode() {
+        let mut histogram = WindowHistogram::new();
+        [0, 10, 10, 20, 30, 40, 250, 10, 255]
+            .iter()
+            .for_each(|v| histogram.add(*v));
+
+        assert_eq!(histogram.evaluate(Statistic::Mode), 10);
+        // 0 and 10, 250 and 255 get thrown away
+        assert_eq!(histogram.evaluate(Statistic::AlphaTrimmedMean(0.25)), 22);
+        assert_eq!(histogram.evaluate(Statistic::AlphaTrimmedMean(0.0)), 69);
+    }
 }
@@ -0,0 +1,595 @@
+use super::*;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::f64::consts::PI;
+
+/// Fraction of its circumference a circle needs edge pixels on to be found by `hough_circles`
+const CIRCLE_COVERAGE: f64 = 0.5;
+
+/// Seed of the order the probabilistic Hough transform visits the edge pixels in, fixed so the
+/// result is reproducible
+const SEGMENT_SEED: u64 = 0x4006;
+
+/// A line in normal form, the points (x, y) with `x * cos(theta) + y * sin(theta) = rho`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Line {
+    pub rho: f64,
+    /// In [0, pi)
+    pub theta: f64,
+    /// How many edge pixels lie on the line
+    pub votes: u32,
+}
+
+/// A line segment between two edge pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineSegment {
+    pub x0: f64,
+    pub y0: f64,
+    pub x1: f64,
+    pub y1: f64,
+}
+
+impl LineSegment {
+    pub fn length(&self) -> f64 {
+        f64::hypot(self.x1 - self.x0, self.y1 - self.y0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Circle {
+    pub x: f64,
+    pub y: f64,
+    pub radius: f64,
+    /// Fraction of the circumference edge pixels were found on
+    pub coverage: f64,
+}
+
+/// The shapes a Hough transform found, together with the accumulator they were voted in
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hough<T> {
+    /// Strongest first
+    pub shapes: Vec<T>,
+    pub accumulator: FloatImage,
+}
+
+impl<T> Hough<T> {
+    /// The accumulator as a greyscale image, the bin with the most votes is white
+    pub fn accumulator_image(&self) -> CanvasImage {
+        self.accumulator.normalized().to_greyscale()
+    }
+}
+
+/// The (rho, theta) parameter space of lines through an image, the accumulator has one column
+/// per theta and one row per rho
+struct LineSpace {
+    rho_res: f64,
+    /// The bin of rho = 0, every line through the image has |rho| at most this many bins
+    origin: f64,
+    /// (cos, sin) of every theta
+    directions: Vec<(f64, f64)>,
+    theta_res: f64,
+}
+
+impl LineSpace {
+    fn new(width: u32, height: u32, rho_res: f64, theta_res: f64) -> LineSpace {
+        assert!(rho_res > 0.0, "rho_res must be positive");
+        assert!(theta_res > 0.0, "theta_res must be positive");
+
+        let thetas = (PI / theta_res).ceil() as usize;
+        LineSpace {
+            rho_res,
+            origin: (f64::hypot(width as f64, height as f64) / rho_res).ceil(),
+            directions: (0..thetas)
+                .map(|i| {
+                    let (sin, cos) = (i as f64 * theta_res).sin_cos();
+                    (cos, sin)
+                })
+                .collect(),
+            theta_res,
+        }
+    }
+
+    fn accumulator(&self) -> FloatImage {
+        let rhos = 2 * self.origin as u32 + 1;
+        FloatImage::new(self.directions.len() as u32, rhos)
+    }
+
+    /// The accumulator bin of the line through (x, y) at every theta
+    fn bins(&self, (x, y): (u32, u32)) -> impl Iterator<Item = (u32, u32)> + '_ {
+        self.directions
+            .iter()
+            .enumerate()
+            .map(move |(theta, (cos, sin))| {
+                let rho = x as f64 * cos + y as f64 * sin;
+                let bin = (rho / self.rho_res).round() + self.origin;
+                (theta as u32, bin as u32)
+            })
+    }
+
+    fn vote(&self, accumulator: &mut FloatImage, point: (u32, u32), weight: f64) {
+        for (theta, rho) in self.bins(point) {
+            let votes = accumulator.get(theta, rho).unwrap();
+            accumulator.set(theta, rho, votes + weight);
+        }
+    }
+
+    fn line(&self, (theta, rho): (u32, u32), votes: u32) -> Line {
+        Line {
+            rho: (rho as f64 - self.origin) * self.rho_res,
+            theta: theta as f64 * self.theta_res,
+            votes,
+        }
+    }
+}
+
+/// Local maxima of `accumulator` with at least `threshold` votes, strongest first, skipping the
+/// ones that are within 2 bins of a stronger one. The line at theta = pi - e is the one at
+/// theta = -e with the opposite rho, so the theta axis wraps around with rho mirrored.
+fn peaks(accumulator: &FloatImage, threshold: f64) -> Vec<Keypoint> {
+    let (thetas, rhos) = (
+        accumulator.horizontal_size() as f64,
+        accumulator.vertical_size() as f64,
+    );
+    let mirrored = |p: &Keypoint| Keypoint {
+        x: p.x - thetas,
+        y: rhos - 1.0 - p.y,
+        ..*p
+    };
+
+    let mut peaks: Vec<Keypoint> = Vec::new();
+    for peak in features::local_maxima(accumulator, threshold - 0.5) {
+        let close = |p: &Keypoint| {
+            [
+                p.distance(&peak),
+                mirrored(p).distance(&peak),
+                p.distance(&mirrored(&peak)),
+            ]
+            .iter()
+            .any(|distance| *distance <= 2.0)
+        };
+        if !peaks.iter().any(close) {
+            peaks.push(peak);
+        }
+    }
+
+    peaks
+}
+
+/// Unit vector along the line that fits `points` best in the least squares sense
+fn principal_direction(points: &[&(u32, u32)]) -> (f64, f64) {
+    let n = points.len() as f64;
+    let (mx, my) = points.iter().fold((0.0, 0.0), |(mx, my), (x, y)| {
+        (mx + *x as f64 / n, my + *y as f64 / n)
+    });
+    let (sxx, sxy, syy) = points
+        .iter()
+        .fold((0.0, 0.0, 0.0), |(sxx, sxy, syy), (x, y)| {
+            let (dx, dy) = (*x as f64 - mx, *y as f64 - my);
+            (sxx + dx * dx, sxy + dx * dy, syy + dy * dy)
+        });
+
+    let angle = 0.5 * f64::atan2(2.0 * sxy, sxx - syy);
+    (angle.cos(), angle.sin())
+}
+
+/// The distinct pixel offsets on a circle of radius `radius`
+fn circle_offsets(radius: u32) -> Vec<(i64, i64)> {
+    let steps = (2.0 * PI * radius as f64).ceil() as usize * 2;
+    let mut offsets: Vec<_> = (0..steps)
+        .map(|i| {
+            let (sin, cos) = (2.0 * PI * i as f64 / steps as f64).sin_cos();
+            (
+                (radius as f64 * cos).round() as i64,
+                (radius as f64 * sin).round() as i64,
+            )
+        })
+        .collect();
+
+    offsets.sort();
+    offsets.dedup();
+    offsets
+}
+
+impl CanvasImage {
+    /// The foreground pixels of a binary edge map, e.g. from `canny`
+    fn edge_pixels(&self) -> Vec<(u32, u32)> {
+        let foreground = self.foreground();
+        iproduct!(0..self.height, 0..self.width)
+            .filter(|(y, x)| foreground[(y * self.width + x) as usize])
+            .map(|(y, x)| (x, y))
+            .collect()
+    }
+
+    /// Standard Hough transform for lines, treating the image as a binary edge map
+    ///
+    /// # Arguments
+    /// * rho_res: size of the distance bins, in pixels
+    /// * theta_res: size of the angle bins, in radians
+    /// * threshold: how many edge pixels a line needs
+    pub fn hough_lines(&self, rho_res: f64, theta_res: f64, threshold: u32) -> Hough<Line> {
+        let space = LineSpace::new(self.width, self.height, rho_res, theta_res);
+        let mut accumulator = space.accumulator();
+
+        for point in self.edge_pixels() {
+            space.vote(&mut accumulator, point, 1.0);
+        }
+
+        let shapes = peaks(&accumulator, threshold as f64)
+            .into_iter()
+            .map(|p| space.line((p.x as u32, p.y as u32), p.response as u32))
+            .collect();
+
+        Hough {
+            shapes,
+            accumulator,
+        }
+    }
+
+    /// Progressive probabilistic Hough transform (Matas et al.), finds line segments instead of
+    /// infinite lines. Edge pixels vote in random order, as soon as a line gets `threshold`
+    /// votes the segment through the pixel that voted last is traced and its pixels are taken
+    /// out of the accumulator.
+    ///
+    /// # Arguments
+    /// * rho_res, theta_res, threshold: as in `hough_lines`
+    /// * min_length: shorter segments are dropped
+    /// * max_gap: how many pixels in a row can be missing before a segment ends
+    pub fn hough_line_segments(
+        &self,
+        rho_res: f64,
+        theta_res: f64,
+        threshold: u32,
+        min_length: f64,
+        max_gap: u32,
+    ) -> Hough<LineSegment> {
+        let space = LineSpace::new(self.width, self.height, rho_res, theta_res);
+        let mut accumulator = space.accumulator();
+
+        let mut points = self.edge_pixels();
+        points.shuffle(&mut StdRng::seed_from_u64(SEGMENT_SEED));
+
+        let index = |(x, y): (u32, u32)| (y * self.width + x) as usize;
+        let mut unused = vec![false; (self.width * self.height) as usize];
+        points.iter().for_each(|p| unused[index(*p)] = true);
+        let mut voted = vec![false; unused.len()];
+        let mut voters = Vec::new();
+
+        let mut shapes = Vec::new();
+        for point in points {
+            if !unused[index(point)] {
+                continue;
+            }
+
+            space.vote(&mut accumulator, point, 1.0);
+            voted[index(point)] = true;
+            voters.push(point);
+
+            let (bin, votes) = space
+                .bins(point)
+                .map(|bin| (bin, accumulator.get(bin.0, bin.1).unwrap()))
+                .max_by(|a, b| a.1.total_cmp(&b.1))
+                .unwrap();
+            if votes < threshold as f64 {
+                continue;
+            }
+
+            // with few votes the bin is only a rough estimate of the direction, so fit a line to
+            // the pixels that voted for it and walk along that from the point in both directions
+            let on_line: Vec<_> = voters
+                .iter()
+                .filter(|p| voted[index(**p)] && space.bins(**p).any(|b| b == bin))
+                .collect();
+            let (dx, dy) = principal_direction(&on_line);
+            let mut ends = [(point, Vec::new()), (point, Vec::new())];
+
+            for ((end, visited), direction) in ends.iter_mut().zip([1.0, -1.0]) {
+                let mut gap = 0;
+                for step in 1.. {
+                    let x = (point.0 as f64 + direction * step as f64 * dx).round();
+                    let y = (point.1 as f64 + direction * step as f64 * dy).round();
+                    if x < 0.0 || y < 0.0 || x >= self.width as f64 || y >= self.height as f64 {
+                        break;
+                    }
+
+                    let pixel = (x as u32, y as u32);
+                    if unused[index(pixel)] {
+                        *end = pixel;
+                        visited.push(pixel);
+                        gap = 0;
+                    } else {
+                        gap += 1;
+                        if gap > max_gap {
+                            break;
+                        }
+                    }
+                }
+            }
+
+            let [(start, before), (end, after)] = ends;
+            let segment = LineSegment {
+                x0: start.0 as f64,
+                y0: start.1 as f64,
+                x1: end.0 as f64,
+                y1: end.1 as f64,
+            };
+
+            // take the pixels of the segment out, whether it is kept or not
+            for pixel in before.into_iter().chain(after).chain([point]) {
+                unused[index(pixel)] = false;
+                if voted[index(pixel)] {
+                    space.vote(&mut accumulator, pixel, -1.0);
+                    voted[index(pixel)] = false;
+                }
+            }
+
+            if segment.length() >= min_length {
+                shapes.push(segment);
+            }
+        }
+
+        shapes.sort_by(|a, b| b.length().total_cmp(&a.length()));
+        Hough {
+            shapes,
+            accumulator,
+        }
+    }
+
+    /// Hough transform for circles, treating the image as a binary edge map. Every edge pixel
+    /// votes for all the centers it could be on a circle around, for every radius in
+    /// [`r_min`, `r_max`]. A circle is found when edge pixels cover at least half of it.
+    ///
+    /// The accumulator image holds, for every center, the best coverage over all radii.
+    pub fn hough_circles(&self, r_min: u32, r_max: u32) -> Hough<Circle> {
+        assert!(0 < r_min && r_min <= r_max, "need 0 < r_min <= r_max");
+
+        let points = self.edge_pixels();
+        let (width, height) = (self.width as i64, self.height as i64);
+
+        // the coverage of every center for one radius
+        let vote = |radius: u32| {
+            let offsets = circle_offsets(radius);
+            let mut plane = FloatImage::new(self.width, self.height);
+            let weight = 1.0 / offsets.len() as f64;
+
+            for ((x, y), (dx, dy)) in iproduct!(points.iter(), offsets.iter()) {
+                let (cx, cy) = (*x as i64 + dx, *y as i64 + dy);
+                if (0..width).contains(&cx) && (0..height).contains(&cy) {
+                    let coverage = plane.get(cx as u32, cy as u32).unwrap();
+                    plane.set(cx as u32, cy as u32, coverage + weight);
+                }
+            }
+
+            plane
+        };
+
+        // maxima across neighbouring radii as well as neighbouring centers, which only needs the
+        // planes of the radius before and after, so only three planes are kept at any time
+        let mut accumulator = FloatImage::new(self.width, self.height);
+        let mut candidates: Vec<Circle> = Vec::new();
+        let mut previous: Option<FloatImage> = None;
+        let mut current = vote(r_min);
+        for radius in r_min..=r_max {
+            let next = (radius < r_max).then(|| vote(radius + 1));
+            let window = [previous.as_ref(), Some(&current), next.as_ref()];
+
+            for (y, x) in iproduct!(0..height, 0..width) {
+                let value = current.get(x as u32, y as u32).unwrap();
+                let best = accumulator.get(x as u32, y as u32).unwrap();
+                accumulator.set(x as u32, y as u32, best.max(value));
+
+                if value < CIRCLE_COVERAGE {
+                    continue;
+                }
+
+                let is_maximum = iproduct!(0..3, -1..=1, -1..=1)
+                    .filter(|n| *n != (1, 0, 0))
+                    .filter_map(|(r, dy, dx)| Some((window[r]?, dy, dx)))
+                    .all(|(plane, dy, dx)| value >= plane.clamped(x + dx, y + dy));
+                if is_maximum {
+                    candidates.push(Circle {
+                        x: x as f64,
+                        y: y as f64,
+                        radius: radius as f64,
+                        coverage: value,
+                    });
+                }
+            }
+
+            previous = Some(current);
+            current = match next {
+                Some(next) => next,
+                None => break,
+            };
+        }
+
+        candidates.sort_by(|a, b| b.coverage.total_cmp(&a.coverage));
+
+        // concentric duplicates of the same circle, one radius off, are the same circle
+        let mut shapes: Vec<Circle> = Vec::new();
+        for candidate in candidates {
+            let duplicate = shapes.iter().any(|c| {
+                f64::hypot(c.x - candidate.x, c.y - candidate.y) <= 2.0
+                    && (c.radius - candidate.radius).abs() <= 2.0
+            });
+            if !duplicate {
+                shapes.push(candidate);
+            }
+        }
+
+        Hough {
+            shapes,
+            accumulator,
+        }
+    }
+}
+
+fn check_resolutions(rho_res: f64, theta_res: f64) -> Result<(), String> {
+    if rho_res > 0.0 && theta_res > 0.0 {
+        Ok(())
+    } else {
+        Err(format!(
+            "the resolutions must be positive, got {rho_res} and {theta_res}"
+        ))
+    }
+}
+
+fn check_radii(r_min: u32, r_max: u32) -> Result<(), String> {
+    if 0 < r_min && r_min <= r_max {
+        Ok(())
+    } else {
+        Err(format!("need 0 < r_min <= r_max, got {r_min} and {r_max}"))
+    }
+}
+
+/// # Returns
+///  the lines as `[rho0, theta0, rho1, theta1, ...]`, strongest first
+#[wasm_bindgen]
+pub fn hough_lines(
+    edge_map: ImageData,
+    rho_res: f64,
+    theta_res: f64,
+    threshold: u32,
+) -> Result<Vec<f64>, String> {
+    check_resolutions(rho_res, theta_res)?;
+    let edge_map = CanvasImage::from_image_data(edge_map);
+    let lines = edge_map
+        .hough_lines(rho_res, theta_res, threshold)
+        .shapes
+        .iter()
+        .flat_map(|line| [line.rho, line.theta])
+        .collect();
+
+    Ok(lines)
+}
+
+/// The (rho, theta) accumulator of `hough_lines`, theta goes from left to right and rho from top
+/// to bottom
+#[wasm_bindgen]
+pub fn hough_lines_accumulator(
+    edge_map: ImageData,
+    rho_res: f64,
+    theta_res: f64,
+) -> Result<ImageData, String> {
+    check_resolutions(rho_res, theta_res)?;
+    let edge_map = CanvasImage::from_image_data(edge_map);
+    Ok(edge_map
+        .hough_lines(rho_res, theta_res, u32::MAX)
+        .accumulator_image()
+        .into())
+}
+
+/// # Returns
+///  the segments as `[x0, y0, x1, y1, ...]`, longest first
+#[wasm_bindgen]
+pub fn hough_line_segments(
+    edge_map: ImageData,
+    rho_res: f64,
+    theta_res: f64,
+    threshold: u32,
+    min_length: f64,
+    max_gap: u32,
+) -> Result<Vec<f64>, String> {
+    check_resolutions(rho_res, theta_res)?;
+    let edge_map = CanvasImage::from_image_data(edge_map);
+    let segments = edge_map
+        .hough_line_segments(rho_res, theta_res, threshold, min_length, max_gap)
+        .shapes
+        .iter()
+        .flat_map(|s| [s.x0, s.y0, s.x1, s.y1])
+        .collect();
+
+    Ok(segments)
+}
+
+/// # Returns
+///  the circles as `[x0, y0, radius0, x1, ...]`, best covered first
+#[wasm_bindgen]
+pub fn hough_circles(edge_map: ImageData, r_min: u32, r_max: u32) -> Result<Vec<f64>, String> {
+    check_radii(r_min, r_max)?;
+    let edge_map = CanvasImage::from_image_data(edge_map);
+    let circles = edge_map
+        .hough_circles(r_min, r_max)
+        .shapes
+        .iter()
+        .flat_map(|c| [c.x, c.y, c.radius])
+        .collect();
+
+    Ok(circles)
+}
+
+/// The center accumulator of `hough_circles`
+#[wasm_bindgen]
+pub fn hough_circles_accumulator(
+    edge_map: ImageData,
+    r_min: u32,
+    r_max: u32,
+) -> Result<ImageData, String> {
+    check_radii(r_min, r_max)?;
+    let edge_map = CanvasImage::from_image_data(edge_map);
+    Ok(edge_map
+        .hough_circles(r_min, r_max)
+        .accumulator_image()
+        .into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A black `size` x `size` binary image with the pixels `on` returns true for in white
+    fn binary(size: u32, on: impl Fn(i64, i64) -> bool) -> CanvasImage {
+        let mask = iproduct!(0..size as i64, 0..size as i64)
+            .map(|(y, x)| on(x, y))
+            .collect();
+        CanvasImage::from_foreground(mask, size, size)
+    }
+
+    #[test]
+    fn lines_through_a_cross() {
+        // a horizontal line at y = 10 and a vertical one at x = 25
+        let edges = binary(40, |x, y| y == 10 || x == 25);
+        let hough = edges.hough_lines(1.0, PI / 180.0, 30);
+
+        assert_eq!(hough.shapes.len(), 2);
+        for (line, (rho, theta)) in hough.shapes.iter().zip([(10.0, PI / 2.0), (25.0, 0.0)]) {
+            assert!((line.rho - rho).abs() <= 1.0, "{line:?}");
+            assert!((line.theta - theta).abs() < 1e-9, "{line:?}");
+            assert!(line.votes >= 39);
+        }
+
+        let accumulator = hough.accumulator_image();
+        assert_eq!(accumulator.horizontal_size(), 180);
+    }
+
+    #[test]
+    fn segments_stop_at_the_gap() {
+        // two pieces of the same row, 15 and 10 pixels long, 8 pixels apart
+        let edges = binary(40, |x, y| {
+            y == 20 && ((2..17).contains(&x) || (25..35).contains(&x))
+        });
+        let hough = edges.hough_line_segments(1.0, PI / 180.0, 5, 5.0, 3);
+
+        assert_eq!(hough.shapes.len(), 2);
+        let mut xs: Vec<_> = hough
+            .shapes
+            .iter()
+            .map(|s| (s.x0.min(s.x1), s.x0.max(s.x1)))
+            .collect();
+        xs.sort_by(|a, b| a.0.total_cmp(&b.0));
+        assert_eq!(xs, vec![(2.0, 16.0), (25.0, 34.0)]);
+        assert!(hough.shapes.iter().all(|s| s.y0 == 20.0 && s.y1 == 20.0));
+    }
+
+    #[test]
+    fn circle_is_found() {
+        let offsets = circle_offsets(8);
+        let edges = binary(30, |x, y| offsets.contains(&(x - 14, y - 12)));
+        let hough = edges.hough_circles(5, 11);
+
+        assert_eq!(hough.shapes.len(), 1);
+        let circle = hough.shapes[0];
+        assert_eq!((circle.x, circle.y, circle.radius), (14.0, 12.0, 8.0));
+        assert!((circle.coverage - 1.0).abs() < 1e-9);
+    }
+}
@@ -94,6 +94,14 @@ impl CanvasImage {
         }
     }
 
+    /// returns an iterator over `channel`
+    pub fn channel_iter(&self, channel: Channel) -> ChannelIterator<'_> {
+        ChannelIterator {
+            iter: self.data.chunks_exact(4),
+            offset: channel.offset() as u8,
+        }
+    }
+
     /// honestly don't know why you would ever want an iterator over the alpha channel but ok
     pub fn a_iter(&self) -> ChannelIterator {
         ChannelIterator {
@@ -0,0 +1,178 @@
+use super::*;
+
+/// Which values a histogram operation works on
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum HistogramChannels {
+    /// Red, green and blue separately, which can shift the colors
+    Rgb,
+    /// Only the CIELAB lightness, hue and chroma stay as they are
+    #[default]
+    Lightness,
+}
+
+impl CanvasImage {
+    /// The image in CIELAB, with the lightness quantized to 256 levels for histograms
+    pub(super) fn lightness_levels(&self) -> ([FloatImage; 3], Vec<u8>) {
+        let planes = self.to_planes(ColorSpace::Lab);
+        let levels = planes[0]
+            .data()
+            .iter()
+            .map(|l| (l / 100.0 * 255.0).round().clamp(0.0, 255.0) as u8)
+            .collect();
+
+        (planes, levels)
+    }
+
+    /// The image with the lightness in `planes` replaced by `levels`, in [0, 255]
+    pub(super) fn with_lightness_levels(
+        &self,
+        planes: [FloatImage; 3],
+        levels: impl IntoIterator<Item = f64>,
+    ) -> CanvasImage {
+        let [_, a, b] = planes;
+        let lightness = levels.into_iter().map(|v| v / 255.0 * 100.0).collect();
+        let lightness = FloatImage::from_vec_with_size(lightness, self.width, self.height);
+
+        let mut image = CanvasImage::from_vec_with_size(self.data.clone(), self.width, self.height);
+        image.set_planes(&[lightness, a, b], ColorSpace::Lab);
+        image
+    }
+
+    /// Match red, green and blue each to the histogram `target` gives for it
+    fn match_channels(&self, target: impl Fn(Channel) -> Histogram) -> CanvasImage {
+        let mut matched =
+            CanvasImage::from_vec_with_size(self.data.clone(), self.width, self.height);
+        for channel in Channel::COLOR {
+            let source = Histogram::from_values(self.channel_iter(channel));
+            matched = matched.apply_table(&[channel], &source.matching_table(&target(channel)));
+        }
+
+        matched
+    }
+
+    /// Histogram matching: remap the values so their distribution follows the one of
+    /// `reference`, e.g. to give a photo the tones of another one. The images don't have to be
+    /// the same size.
+    pub fn match_histogram(
+        &self,
+        reference: &CanvasImage,
+        channels: HistogramChannels,
+    ) -> CanvasImage {
+        match channels {
+            HistogramChannels::Rgb => self
+                .match_channels(|channel| Histogram::from_values(reference.channel_iter(channel))),
+            HistogramChannels::Lightness => {
+                let (_, target) = reference.lightness_levels();
+                self.match_histogram_to(&Histogram::from_values(target), channels)
+            }
+        }
+    }
+
+    /// Histogram matching to an arbitrary `target` distribution, applied to each of red, green
+    /// and blue or to the lightness, where the 256 buckets cover L from 0 to 100
+    pub fn match_histogram_to(
+        &self,
+        target: &Histogram,
+        channels: HistogramChannels,
+    ) -> CanvasImage {
+        match channels {
+            HistogramChannels::Rgb => self.match_channels(|_| target.clone()),
+            HistogramChannels::Lightness => {
+                let (planes, levels) = self.lightness_levels();
+                let table = Histogram::from_values(levels.iter().copied()).matching_table(target);
+
+                let matched = levels.into_iter().map(|l| table[l as usize] as f64);
+                self.with_lightness_levels(planes, matched)
+            }
+        }
+    }
+}
+
+#[wasm_bindgen]
+pub fn match_histogram(
+    image: ImageData,
+    reference: ImageData,
+    channels: HistogramChannels,
+) -> Result<ImageData, String> {
+    let image = CanvasImage::from_image_data(image);
+    let reference = CanvasImage::from_image_data(reference);
+    if reference.rgba_slice().is_empty() {
+        return Err("the reference image is empty".to_string());
+    }
+
+    Ok(image.match_histogram(&reference, channels).into())
+}
+
+/// # Arguments
+/// * buckets: the target distribution, 256 counts or weights that aren't negative and don't
+///   all add up to 0
+#[wasm_bindgen]
+pub fn match_histogram_to(
+    image: ImageData,
+    buckets: &[f64],
+    channels: HistogramChannels,
+) -> Result<ImageData, String> {
+    if buckets.len() != 256 {
+        return Err(format!("expected 256 buckets, got {}", buckets.len()));
+    }
+    if buckets.iter().any(|b| !b.is_finite() || *b < 0.0) {
+        return Err("the buckets must be finite and not negative".to_string());
+    }
+    if buckets.iter().sum::<f64>() <= 0.0 {
+        return Err("the buckets must not all be 0".to_string());
+    }
+
+    let image = CanvasImage::from_image_data(image);
+    let target = Histogram::from_buckets(buckets.to_vec());
+    Ok(image.match_histogram_to(&target, channels).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grey_ramp(values: impl Iterator<Item = u8>, width: u32) -> CanvasImage {
+        let rgba: Vec<u8> = values.flat_map(|v| [v, v, v, 255]).collect();
+        let height = rgba.len() as u32 / 4 / width;
+        CanvasImage::from_vec_with_size(rgba, width, height)
+    }
+
+    #[test]
+    fn takes_on_the_reference_distribution() {
+        let dark = grey_ramp(0..64, 8);
+        let bright = grey_ramp((0..64).map(|v| 128 + v * 2), 8);
+
+        // with as many distinct values on both sides the values are swapped one for one
+        let matched = dark.match_histogram(&bright, HistogramChannels::Rgb);
+        assert_eq!(matched.rgba_slice(), bright.rgba_slice());
+
+        // matching to itself changes nothing
+        let same = bright.match_histogram(&bright, HistogramChannels::Rgb);
+        assert_eq!(same.rgba_slice(), bright.rgba_slice());
+
+        let mut buckets = vec![0.0; 256];
+        buckets[200] = 1.0;
+        let flat =
+            dark.match_histogram_to(&Histogram::from_buckets(buckets), HistogramChannels::Rgb);
+        assert!(flat.rgba_iter().all(|p| p == (200, 200, 200, 255)));
+    }
+
+    #[test]
+    fn lightness_keeps_the_colors() {
+        let image = CanvasImage::from_vec_with_size(
+            [[40, 20, 10, 255], [80, 40, 20, 255], [30, 30, 30, 100]].concat(),
+            3,
+            1,
+        );
+        let reference = grey_ramp([100, 180, 250].into_iter(), 3);
+
+        let matched = image.match_histogram(&reference, HistogramChannels::Lightness);
+        let (r, g, b, a) = matched.rgba(2, 0).unwrap();
+        assert!(r == g && g == b && a == 100);
+
+        // the brown got brighter but stayed brown
+        let (r, g, b, _) = matched.rgba(1, 0).unwrap();
+        assert!(r > 80 && r > g && g > b);
+    }
+}
@@ -0,0 +1,475 @@
+use super::*;
+use crate::distance::{DistanceMetric, Euclidean};
+
+/// A flat structuring element, the shape morphological operations probe the image with. The
+/// origin is always the center, i.e. (width / 2, height / 2).
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StructuringElement {
+    mask: Vec<bool>,
+    width: usize,
+    height: usize,
+}
+
+#[wasm_bindgen]
+impl StructuringElement {
+    /// A width x height rectangle
+    pub fn rect(width: usize, height: usize) -> StructuringElement {
+        StructuringElement {
+            mask: vec![true; width * height],
+            width,
+            height,
+        }
+    }
+
+    /// A plus sign with arms of length `radius`
+    pub fn cross(radius: usize) -> StructuringElement {
+        let size = 2 * radius + 1;
+        let mask = iproduct!(0..size, 0..size)
+            .map(|(y, x)| x == radius || y == radius)
+            .collect();
+
+        StructuringElement {
+            mask,
+            width: size,
+            height: size,
+        }
+    }
+
+    /// Every point within `radius` of the center in Euclidean distance
+    pub fn disk(radius: usize) -> StructuringElement {
+        let size = 2 * radius + 1;
+        let center = radius as u32;
+        let mask = iproduct!(0..size as u32, 0..size as u32)
+            .map(|(y, x)| Euclidean.distance(x, y, center, center) <= radius as f64)
+            .collect();
+
+        StructuringElement {
+            mask,
+            width: size,
+            height: size,
+        }
+    }
+
+    /// Build an arbitrary element from a row major mask, every non zero entry is part of it
+    pub fn from_mask(mask: Vec<u8>, width: usize, height: usize) -> StructuringElement {
+        assert_eq!(mask.len(), width * height, "mask must be width x height");
+
+        StructuringElement {
+            mask: mask.into_iter().map(|v| v != 0).collect(),
+            width,
+            height,
+        }
+    }
+}
+
+impl StructuringElement {
+    /// Offsets of every point of the element relative to its origin
+    pub fn offsets(&self) -> Vec<(i64, i64)> {
+        let (cx, cy) = ((self.width / 2) as i64, (self.height / 2) as i64);
+
+        iproduct!(0..self.height, 0..self.width)
+            .filter(|(y, x)| self.mask[y * self.width + x])
+            .map(|(y, x)| (x as i64 - cx, y as i64 - cy))
+            .collect()
+    }
+}
+
+impl CanvasImage {
+    /// Take the min (or max) of every color channel over the element placed at each pixel, the
+    /// part of the element that falls outside of the image is ignored. Alpha is left untouched.
+    fn rank_over(&self, offsets: &[(i64, i64)], max: bool) -> CanvasImage {
+        let (width, height) = (self.width as i64, self.height as i64);
+        let mut result =
+            CanvasImage::from_vec_with_size(self.data.clone(), self.width, self.height);
+
+        for (y, x) in iproduct!(0..height, 0..width) {
+            let neighbours = offsets
+                .iter()
+                .map(|(dx, dy)| (x + dx, y + dy))
+                .filter(|(x, y)| (0..width).contains(x) && (0..height).contains(y))
+                .map(|(x, y)| (4 * (y * width + x)) as usize);
+
+            let index = (4 * (y * width + x)) as usize;
+            for channel in Channel::COLOR {
+                let values = neighbours.clone().map(|i| self.data[i + channel.offset()]);
+                let value = if max { values.max() } else { values.min() };
+
+                if let Some(value) = value {
+                    result.data[index + channel.offset()] = value;
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Subtract the color channels of `other` from this image, saturating at 0
+    fn saturating_difference(&self, other: &CanvasImage) -> CanvasImage {
+        let rgba = Iterator::zip(self.data.chunks_exact(4), other.data.chunks_exact(4)).flat_map(
+            |(a, b)| {
+                [
+                    a[0].saturating_sub(b[0]),
+                    a[1].saturating_sub(b[1]),
+                    a[2].saturating_sub(b[2]),
+                    a[3],
+                ]
+            },
+        );
+
+        CanvasImage::from_vec_with_size(rgba.collect(), self.width, self.height)
+    }
+
+    /// Greyscale erosion, every pixel becomes the darkest value under the element. On a binary
+    /// image this shrinks the white regions.
+    pub fn erode(&self, element: &StructuringElement) -> CanvasImage {
+        self.rank_over(&element.offsets(), false)
+    }
+
+    /// Greyscale dilation, every pixel becomes the brightest value under the reflected element.
+    /// On a binary image this grows the white regions.
+    pub fn dilate(&self, element: &StructuringElement) -> CanvasImage {
+        let reflected: Vec<_> = element
+            .offsets()
+            .into_iter()
+            .map(|(dx, dy)| (-dx, -dy))
+            .collect();
+
+        self.rank_over(&reflected, true)
+    }
+
+    /// Erosion followed by dilation, removes bright details smaller than the element
+    pub fn open(&self, element: &StructuringElement) -> CanvasImage {
+        self.erode(element).dilate(element)
+    }
+
+    /// Dilation followed by erosion, fills dark details smaller than the element
+    pub fn close(&self, element: &StructuringElement) -> CanvasImage {
+        self.dilate(element).erode(element)
+    }
+
+    /// Dilation minus erosion, bright wherever the intensity changes within the element
+    pub fn morphological_gradient(&self, element: &StructuringElement) -> CanvasImage {
+        self.dilate(element)
+            .saturating_difference(&self.erode(element))
+    }
+
+    /// The image minus its opening, keeps the bright details smaller than the element
+    pub fn top_hat(&self, element: &StructuringElement) -> CanvasImage {
+        self.saturating_difference(&self.open(element))
+    }
+
+    /// The closing minus the image, keeps the dark details smaller than the element
+    pub fn black_hat(&self, element: &StructuringElement) -> CanvasImage {
+        self.close(element).saturating_difference(self)
+    }
+
+    /// Hit-or-miss transform of a binary image, a pixel is white only if `hit` fits entirely
+    /// inside the foreground and `miss` fits entirely inside the background around it
+    pub fn hit_or_miss(&self, hit: &StructuringElement, miss: &StructuringElement) -> CanvasImage {
+        let (width, height) = (self.width as i64, self.height as i64);
+        let foreground = self.foreground();
+
+        // outside of the image counts as background
+        let is_foreground = |x: i64, y: i64| {
+            (0..width).contains(&x)
+                && (0..height).contains(&y)
+                && foreground[(y * width + x) as usize]
+        };
+
+        let hit = hit.offsets();
+        let miss = miss.offsets();
+
+        let mask = iproduct!(0..height, 0..width)
+            .map(|(y, x)| {
+                hit.iter().all(|(dx, dy)| is_foreground(x + dx, y + dy))
+                    && miss.iter().all(|(dx, dy)| !is_foreground(x + dx, y + dy))
+            })
+            .collect();
+
+        CanvasImage::from_foreground(mask, self.width, self.height)
+    }
+
+    /// Thin the white regions of a binary image down to 1 pixel wide lines that keep their
+    /// topology, using the Zhang-Suen algorithm
+    pub fn skeletonize(&self) -> CanvasImage {
+        let (width, height) = (self.width as i64, self.height as i64);
+        let mut skeleton = self.foreground();
+
+        let at = |mask: &[bool], x: i64, y: i64| {
+            (0..width).contains(&x) && (0..height).contains(&y) && mask[(y * width + x) as usize]
+        };
+
+        loop {
+            let mut changed = false;
+
+            for pass in 0..2 {
+                let removable: Vec<_> = iproduct!(0..height, 0..width)
+                    .filter(|(y, x)| at(&skeleton, *x, *y))
+                    .filter(|(y, x)| {
+                        // P2 to P9, clockwise starting from the top
+                        let neighbours = [
+                            (0, -1),
+                            (1, -1),
+                            (1, 0),
+                            (1, 1),
+                            (0, 1),
+                            (-1, 1),
+                            (-1, 0),
+                            (-1, -1),
+                        ]
+                        .map(|(dx, dy)| at(&skeleton, x + dx, y + dy));
+                        let [p2, _, p4, _, p6, _, p8, _] = neighbours;
+
+                        let count = neighbours.iter().filter(|p| **p).count();
+                        let transitions = (0..8)
+                            .filter(|i| !neighbours[*i] && neighbours[(i + 1) % 8])
+                            .count();
+
+                        let directional = if pass == 0 {
+                            !(p4 && p6 && (p2 || p8))
+                        } else {
+                            !(p2 && p8 && (p4 || p6))
+                        };
+
+                        (2..=6).contains(&count) && transitions == 1 && directional
+                    })
+                    .collect();
+
+                changed |= !removable.is_empty();
+                for (y, x) in removable {
+                    skeleton[(y * width + x) as usize] = false;
+                }
+            }
+
+            if !changed {
+                break;
+            }
+        }
+
+        CanvasImage::from_foreground(skeleton, self.width, self.height)
+    }
+
+    /// Morphological reconstruction by dilation with this image as the mask, `marker` is grown
+    /// for as long as it stays under the mask. On a binary image this extracts every region
+    /// that the marker touches.
+    pub fn reconstruct(&self, marker: &CanvasImage) -> CanvasImage {
+        assert_eq!(marker.width, self.width, "marker must be the same size");
+        assert_eq!(marker.height, self.height, "marker must be the same size");
+
+        let (width, height) = (self.width as i64, self.height as i64);
+        let index = |x: i64, y: i64| (4 * (y * width + x)) as usize;
+        let in_bounds = |(x, y): &(i64, i64)| (0..width).contains(x) && (0..height).contains(y);
+
+        // the neighbours that have already been visited in a forward raster scan, negate them for
+        // the backward scan
+        let causal = [(-1, -1), (0, -1), (1, -1), (-1, 0)];
+
+        let mut result = marker.pointwise_min(self);
+        for channel in Channel::COLOR {
+            let offset = channel.offset();
+
+            // alternate forward and backward scans until nothing changes any more
+            loop {
+                let mut changed = false;
+
+                for forward in [true, false] {
+                    let pixels: Box<dyn Iterator<Item = (i64, i64)>> = if forward {
+                        Box::new(iproduct!(0..height, 0..width))
+                    } else {
+                        Box::new(iproduct!((0..height).rev(), (0..width).rev()))
+                    };
+
+                    for (y, x) in pixels {
+                        let brightest = causal
+                            .iter()
+                            .map(|(dx, dy)| {
+                                if forward {
+                                    (x + dx, y + dy)
+                                } else {
+                                    (x - dx, y - dy)
+                                }
+                            })
+                            .filter(in_bounds)
+                            .map(|(x, y)| result.data[index(x, y) + offset])
+                            .fold(result.data[index(x, y) + offset], u8::max);
+
+                        let value = brightest.min(self.data[index(x, y) + offset]);
+                        if value != result.data[index(x, y) + offset] {
+                            result.data[index(x, y) + offset] = value;
+                            changed = true;
+                        }
+                    }
+                }
+
+                if !changed {
+                    break;
+                }
+            }
+        }
+
+        result
+    }
+
+    /// The darker of the two images for every color channel, alpha comes from this image
+    fn pointwise_min(&self, other: &CanvasImage) -> CanvasImage {
+        let rgba = Iterator::zip(self.data.chunks_exact(4), other.data.chunks_exact(4))
+            .flat_map(|(a, b)| [a[0].min(b[0]), a[1].min(b[1]), a[2].min(b[2]), a[3]]);
+
+        CanvasImage::from_vec_with_size(rgba.collect(), self.width, self.height)
+    }
+}
+
+#[wasm_bindgen]
+pub fn erode(image: ImageData, element: &StructuringElement) -> ImageData {
+    let image = CanvasImage::from_image_data(image);
+    image.erode(element).into()
+}
+
+#[wasm_bindgen]
+pub fn dilate(image: ImageData, element: &StructuringElement) -> ImageData {
+    let image = CanvasImage::from_image_data(image);
+    image.dilate(element).into()
+}
+
+#[wasm_bindgen]
+pub fn morphological_open(image: ImageData, element: &StructuringElement) -> ImageData {
+    let image = CanvasImage::from_image_data(image);
+    image.open(element).into()
+}
+
+#[wasm_bindgen]
+pub fn morphological_close(image: ImageData, element: &StructuringElement) -> ImageData {
+    let image = CanvasImage::from_image_data(image);
+    image.close(element).into()
+}
+
+#[wasm_bindgen]
+pub fn morphological_gradient(image: ImageData, element: &StructuringElement) -> ImageData {
+    let image = CanvasImage::from_image_data(image);
+    image.morphological_gradient(element).into()
+}
+
+#[wasm_bindgen]
+pub fn top_hat(image: ImageData, element: &StructuringElement) -> ImageData {
+    let image = CanvasImage::from_image_data(image);
+    image.top_hat(element).into()
+}
+
+#[wasm_bindgen]
+pub fn black_hat(image: ImageData, element: &StructuringElement) -> ImageData {
+    let image = CanvasImage::from_image_data(image);
+    image.black_hat(element).into()
+}
+
+#[wasm_bindgen]
+pub fn hit_or_miss(
+    image: ImageData,
+    hit: &StructuringElement,
+    miss: &StructuringElement,
+) -> ImageData {
+    let image = CanvasImage::from_image_data(image);
+    image.hit_or_miss(hit, miss).into()
+}
+
+#[wasm_bindgen]
+pub fn skeletonize(image: ImageData) -> ImageData {
+    let image = CanvasImage::from_image_data(image);
+    image.skeletonize().into()
+}
+
+#[wasm_bindgen]
+pub fn reconstruct(mask: ImageData, marker: ImageData) -> ImageData {
+    let mask = CanvasImage::from_image_data(mask);
+    let marker = CanvasImage::from_image_data(marker);
+    mask.reconstruct(&marker).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas_image::test_util::binary;
+
+    fn assert_binary(image: &CanvasImage, rows: &[&str]) {
+        let expected = binary(rows);
+        assert_eq!(image.foreground(), expected.foreground());
+    }
+
+    #[test]
+    fn structuring_element_shapes() {
+        assert_eq!(
+            StructuringElement::rect(3, 1).offsets(),
+            vec![(-1, 0), (0, 0), (1, 0)]
+        );
+        assert_eq!(StructuringElement::cross(1).offsets().len(), 5);
+        assert_eq!(StructuringElement::disk(2).offsets().len(), 13);
+        assert_eq!(
+            StructuringElement::from_mask(vec![0, 1, 0, 0], 2, 2).offsets(),
+            vec![(0, -1)]
+        );
+    }
+
+    #[test]
+    fn opening_removes_specks_and_closing_fills_holes() {
+        let image = binary(&[
+            "........", ".####...", ".####..#", ".####...", ".####...", "........",
+        ]);
+        let square = StructuringElement::rect(3, 3);
+
+        assert_binary(
+            &image.open(&square),
+            &[
+                "........", ".####...", ".####...", ".####...", ".####...", "........",
+            ],
+        );
+
+        let holey = binary(&["#####", "#####", "##.##", "#####", "#####"]);
+        assert_binary(&holey.close(&square), &["#####"; 5]);
+        assert_binary(
+            &holey.black_hat(&square),
+            &[".....", ".....", "..#..", ".....", "....."],
+        );
+    }
+
+    #[test]
+    fn hit_or_miss_finds_isolated_pixels() {
+        let image = binary(&[".....", ".#...", ".....", "..##.", "....."]);
+        let hit = StructuringElement::rect(1, 1);
+        let miss = StructuringElement::from_mask(vec![1, 1, 1, 1, 0, 1, 1, 1, 1], 3, 3);
+
+        assert_binary(
+            &image.hit_or_miss(&hit, &miss),
+            &[".....", ".#...", ".....", ".....", "....."],
+        );
+    }
+
+    #[test]
+    fn skeleton_of_a_bar_is_a_line() {
+        let image = binary(&[
+            "..........",
+            ".########.",
+            ".########.",
+            ".########.",
+            "..........",
+        ]);
+        let skeleton = image.skeletonize();
+
+        // one pixel thick, and still spans most of the bar
+        for x in 0..10 {
+            let column = (0..5).filter(|y| skeleton.foreground()[y * 10 + x]).count();
+            assert!(column <= 1);
+        }
+        assert!(skeleton.foreground().iter().filter(|p| **p).count() >= 5);
+    }
+
+    #[test]
+    fn reconstruction_keeps_marked_regions() {
+        let mask = binary(&["##...", "##..#", "....#", "##..#"]);
+        let marker = binary(&[".....", ".....", ".....", "....#"]);
+
+        assert_binary(
+            &mask.reconstruct(&marker),
+            &[".....", "....#", "....#", "....#"],
+        );
+    }
+}
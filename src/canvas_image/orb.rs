@@ -0,0 +1,357 @@
+use super::features::{coordinates, local_maxima, StructureTensor};
+use super::*;
+use nalgebra::Point2;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rand_distr::{Distribution, Normal};
+
+/// The 16 pixels on a Bresenham circle of radius 3, in order around the circle
+const FAST_CIRCLE: [(i64, i64); 16] = [
+    (0, -3),
+    (1, -3),
+    (2, -2),
+    (3, -1),
+    (3, 0),
+    (3, 1),
+    (2, 2),
+    (1, 3),
+    (0, 3),
+    (-1, 3),
+    (-2, 2),
+    (-3, 1),
+    (-3, 0),
+    (-3, -1),
+    (-2, -2),
+    (-1, -3),
+];
+
+/// How many contiguous pixels of the circle must all be brighter or all be darker, FAST-9
+const FAST_ARC: usize = 9;
+
+/// Radius of the patch around a keypoint its orientation and descriptor are computed over,
+/// keypoints closer than this to the border are dropped
+const PATCH_RADIUS: i64 = 15;
+
+/// Standard deviation of the smoothing applied before the descriptor tests, BRIEF compares
+/// single pixels so it is very sensitive to noise otherwise
+const DESCRIPTOR_SIGMA: f64 = 2.0;
+
+/// Seed of the BRIEF sampling pattern, every descriptor has to use the same one to be comparable
+const PATTERN_SEED: u64 = 0x0b71ef;
+
+/// A 256 bit binary descriptor, every bit is the result of comparing the intensities of two
+/// points around the keypoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Descriptor(pub [u64; 4]);
+
+impl Descriptor {
+    /// The number of bits that differ
+    pub fn hamming(&self, other: &Descriptor) -> u32 {
+        Iterator::zip(self.0.iter(), other.0.iter())
+            .map(|(a, b)| (a ^ b).count_ones())
+            .sum()
+    }
+}
+
+/// A keypoint together with its orientation and descriptor
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Feature {
+    pub keypoint: Keypoint,
+    /// Direction from the keypoint to the intensity centroid of its patch, in radians
+    pub angle: f64,
+    pub descriptor: Descriptor,
+}
+
+/// A feature of one image matched with a feature of another
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Match {
+    pub from: Keypoint,
+    pub to: Keypoint,
+    /// Hamming distance between the two descriptors
+    pub distance: u32,
+}
+
+/// The matches between the features of two images, best first
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Matches {
+    pub matches: Vec<Match>,
+}
+
+impl Matches {
+    pub fn len(&self) -> usize {
+        self.matches.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.matches.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Match> {
+        self.matches.iter()
+    }
+
+    /// The matched points as two parallel lists, the i-th point of the first image corresponds
+    /// to the i-th point of the second. This is what homography estimation expects.
+    pub fn correspondences(&self) -> (Vec<Point2<f64>>, Vec<Point2<f64>>) {
+        self.matches
+            .iter()
+            .map(|m| (Point2::new(m.from.x, m.from.y), Point2::new(m.to.x, m.to.y)))
+            .unzip()
+    }
+}
+
+/// The pairs of points BRIEF compares, drawn from an isotropic Gaussian around the keypoint
+/// like in the original paper, and clipped to the patch
+fn brief_pattern() -> Vec<((f64, f64), (f64, f64))> {
+    let mut rng = StdRng::seed_from_u64(PATTERN_SEED);
+    let normal = Normal::new(0.0, (2 * PATCH_RADIUS + 1) as f64 / 5.0).unwrap();
+    let limit = PATCH_RADIUS as f64;
+    let mut sample = || normal.sample(&mut rng).round().clamp(-limit, limit);
+
+    (0..256)
+        .map(|_| ((sample(), sample()), (sample(), sample())))
+        .collect()
+}
+
+/// FAST score of the pixel, the sum of how far the circle pixels are beyond the threshold on
+/// the side that forms an arc of at least `FAST_ARC` pixels, `None` if it isn't a corner
+fn fast_score(luminance: &FloatImage, x: i64, y: i64, threshold: f64) -> Option<f64> {
+    let center = luminance.clamped(x, y);
+    let circle = FAST_CIRCLE.map(|(dx, dy)| luminance.clamped(x + dx, y + dy) - center);
+
+    [1.0, -1.0].into_iter().find_map(|side: f64| {
+        let beyond = circle.map(|difference| side * difference > threshold);
+
+        // the arc can wrap around, so walk the circle twice
+        let mut run = 0;
+        let longest = (0..2 * circle.len())
+            .map(|i| {
+                run = if beyond[i % circle.len()] { run + 1 } else { 0 };
+                run
+            })
+            .max()
+            .unwrap_or(0);
+
+        (longest >= FAST_ARC).then(|| {
+            circle
+                .iter()
+                .map(|difference| (side * difference - threshold).max(0.0))
+                .sum()
+        })
+    })
+}
+
+impl CanvasImage {
+    /// FAST-9 corner detector on the luminance of the image: a pixel is a corner if 9 contiguous
+    /// pixels on the circle of radius 3 around it are all brighter or all darker than it by more
+    /// than `threshold`. Only the strongest corner of every 3 x 3 neighbourhood is kept.
+    ///
+    /// # Returns
+    ///  the corners, strongest first
+    pub fn fast_corners(&self, threshold: f64) -> Vec<Keypoint> {
+        let luminance = self.luminance();
+        let score = FloatImage::from_fn(self.width, self.height, |x, y| {
+            fast_score(&luminance, x as i64, y as i64, threshold).unwrap_or(0.0)
+        });
+
+        local_maxima(&score, 0.0)
+    }
+
+    /// ORB features: FAST-9 corners ranked by their Harris response, oriented by the intensity
+    /// centroid of their patch and described with steered BRIEF on the Gaussian smoothed
+    /// luminance. This works on a single scale only.
+    ///
+    /// # Arguments
+    /// * max_features: how many of the strongest corners to keep
+    /// * fast_threshold: the FAST threshold, in [0, 1] intensity units
+    pub fn orb_features(&self, max_features: usize, fast_threshold: f64) -> Vec<Feature> {
+        let luminance = self.luminance();
+        let harris = StructureTensor::of(&luminance, 1.0).harris(0.04);
+        let smoothed = luminance.gaussian_blur(DESCRIPTOR_SIGMA);
+        let pattern = brief_pattern();
+
+        let (width, height) = (self.width as i64, self.height as i64);
+        let mut keypoints: Vec<_> = self
+            .fast_corners(fast_threshold)
+            .into_iter()
+            .filter(|k| {
+                let (x, y) = (k.x as i64, k.y as i64);
+                (PATCH_RADIUS..width - PATCH_RADIUS).contains(&x)
+                    && (PATCH_RADIUS..height - PATCH_RADIUS).contains(&y)
+            })
+            .map(|k| Keypoint {
+                response: harris.get(k.x as u32, k.y as u32).unwrap(),
+                ..k
+            })
+            .collect();
+
+        keypoints.sort_by(|a, b| b.response.total_cmp(&a.response));
+        keypoints.truncate(max_features);
+
+        keypoints
+            .into_iter()
+            .map(|keypoint| {
+                let (x, y) = (keypoint.x as i64, keypoint.y as i64);
+                let sample = |dx: f64, dy: f64| {
+                    smoothed.clamped(x + dx.round() as i64, y + dy.round() as i64)
+                };
+
+                // intensity centroid over the disk of the patch
+                let (m10, m01) =
+                    iproduct!(-PATCH_RADIUS..=PATCH_RADIUS, -PATCH_RADIUS..=PATCH_RADIUS)
+                        .filter(|(dy, dx)| dx * dx + dy * dy <= PATCH_RADIUS * PATCH_RADIUS)
+                        .fold((0.0, 0.0), |(m10, m01), (dy, dx)| {
+                            let v = sample(dx as f64, dy as f64);
+                            (m10 + dx as f64 * v, m01 + dy as f64 * v)
+                        });
+                let angle = m01.atan2(m10);
+
+                // steer the pattern by rotating it with the keypoint
+                let (sin, cos) = angle.sin_cos();
+                let rotate = |(px, py): (f64, f64)| (cos * px - sin * py, sin * px + cos * py);
+
+                let mut descriptor = [0u64; 4];
+                for (i, (p, q)) in pattern.iter().enumerate() {
+                    let (px, py) = rotate(*p);
+                    let (qx, qy) = rotate(*q);
+                    if sample(px, py) < sample(qx, qy) {
+                        descriptor[i / 64] |= 1 << (i % 64);
+                    }
+                }
+
+                Feature {
+                    keypoint,
+                    angle,
+                    descriptor: Descriptor(descriptor),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Brute force matching of binary descriptors with Lowe's ratio test, every feature of `from`
+/// is matched with the feature of `to` closest in Hamming distance, as long as that is clearly
+/// closer than the second closest
+///
+/// # Arguments
+/// * ratio: the closest distance must be below `ratio` times the second closest, usually
+///   around 0.8
+pub fn match_features(from: &[Feature], to: &[Feature], ratio: f64) -> Matches {
+    let mut matches: Vec<_> = from
+        .iter()
+        .filter_map(|a| {
+            let mut best: Option<(u32, &Feature)> = None;
+            let mut second = u32::MAX;
+
+            for b in to {
+                let distance = a.descriptor.hamming(&b.descriptor);
+                match best {
+                    Some((best_distance, _)) if distance >= best_distance => {
+                        second = second.min(distance);
+                    }
+                    _ => {
+                        second = best.map_or(second, |(d, _)| d);
+                        best = Some((distance, b));
+                    }
+                }
+            }
+
+            let (distance, b) = best?;
+            ((distance as f64) < ratio * second as f64).then_some(Match {
+                from: a.keypoint,
+                to: b.keypoint,
+                distance,
+            })
+        })
+        .collect();
+
+    matches.sort_by_key(|m| m.distance);
+    Matches { matches }
+}
+
+/// # Returns
+///  the coordinates of the corners as `[x0, y0, x1, y1, ...]`, strongest first
+#[wasm_bindgen]
+pub fn fast_corners(image: ImageData, threshold: f64) -> Vec<f64> {
+    let image = CanvasImage::from_image_data(image);
+    coordinates(&image.fast_corners(threshold))
+}
+
+/// Detect ORB features in both images and match them
+///
+/// # Returns
+///  the matched points as `[from_x0, from_y0, to_x0, to_y0, from_x1, ...]`, best match first
+#[wasm_bindgen]
+pub fn match_orb_features(
+    from: ImageData,
+    to: ImageData,
+    max_features: usize,
+    fast_threshold: f64,
+    ratio: f64,
+) -> Vec<f64> {
+    let from = CanvasImage::from_image_data(from).orb_features(max_features, fast_threshold);
+    let to = CanvasImage::from_image_data(to).orb_features(max_features, fast_threshold);
+
+    match_features(&from, &to, ratio)
+        .iter()
+        .flat_map(|m| [m.from.x, m.from.y, m.to.x, m.to.y])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas_image::test_util::white_square;
+    use rand::Rng;
+
+    /// An 80 x 80 image of random grey 5 x 5 blocks, shifted by (`dx`, `dy`)
+    fn blocks(dx: i64, dy: i64) -> CanvasImage {
+        let mut rng = StdRng::seed_from_u64(7);
+        let grid: Vec<u8> = (0..20 * 20).map(|_| rng.gen()).collect();
+
+        let rgba = iproduct!(0..80i64, 0..80i64).flat_map(|(y, x)| {
+            let (x, y) = ((x - dx).rem_euclid(100), (y - dy).rem_euclid(100));
+            let v = grid[(y / 5 * 20 + x / 5) as usize];
+            [v, v, v, 255]
+        });
+
+        CanvasImage::from_vec_with_size(rgba.collect(), 80, 80)
+    }
+
+    #[test]
+    fn fast_only_fires_on_corners() {
+        let corners = white_square().fast_corners(0.2);
+        assert_eq!(corners.len(), 4);
+        for corner in corners {
+            assert!([4.0, 5.0, 14.0, 15.0].contains(&corner.x));
+            assert!([4.0, 5.0, 14.0, 15.0].contains(&corner.y));
+        }
+    }
+
+    #[test]
+    fn hamming_distance() {
+        let a = Descriptor([0b1011, 0, u64::MAX, 1]);
+        let b = Descriptor([0b0001, 0, 0, 1]);
+        assert_eq!(a.hamming(&b), 66);
+        assert_eq!(a.hamming(&a), 0);
+    }
+
+    #[test]
+    fn matches_recover_a_translation() {
+        let from = blocks(0, 0).orb_features(100, 0.05);
+        let to = blocks(3, 2).orb_features(100, 0.05);
+
+        let matches = match_features(&from, &to, 0.8);
+        assert!(matches.len() >= 10);
+
+        let consistent = matches
+            .iter()
+            .filter(|m| m.to.x - m.from.x == 3.0 && m.to.y - m.from.y == 2.0)
+            .count();
+        assert!(consistent * 10 >= matches.len() * 9);
+
+        let (a, b) = matches.correspondences();
+        assert_eq!(a.len(), matches.len());
+        assert_eq!(b.len(), matches.len());
+    }
+}
@@ -0,0 +1,290 @@
+use super::*;
+use crate::color_space::srgb_to_lab;
+
+/// How the difference between two colors is measured
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ColorDistance {
+    /// Euclidean distance between the RGB values in [0, 255], alpha included
+    Rgb,
+    /// Euclidean distance in CIELAB (Delta E 1976), closer to how different the colors look.
+    /// 2.3 is about the smallest difference that can be seen
+    Lab,
+}
+
+impl ColorDistance {
+    /// Distance of every pixel of `image` to `color`
+    fn distances(&self, image: &CanvasImage, color: (u8, u8, u8, u8)) -> Vec<f64> {
+        let (r, g, b, a) = color;
+        match self {
+            ColorDistance::Rgb => image
+                .rgba_iter()
+                .map(|(pr, pg, pb, pa)| {
+                    [(pr, r), (pg, g), (pb, b), (pa, a)]
+                        .iter()
+                        .map(|(p, c)| (*p as f64 - *c as f64).powi(2))
+                        .sum::<f64>()
+                        .sqrt()
+                })
+                .collect(),
+            ColorDistance::Lab => {
+                let lab = |r: u8, g: u8, b: u8| {
+                    srgb_to_lab(r as f64 / 255.0, g as f64 / 255.0, b as f64 / 255.0)
+                };
+                let (l, a, b) = lab(r, g, b);
+
+                image
+                    .rgba_iter()
+                    .map(|(pr, pg, pb, _)| {
+                        let (pl, pa, pb) = lab(pr, pg, pb);
+                        ((pl - l).powi(2) + (pa - a).powi(2) + (pb - b).powi(2)).sqrt()
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Scanline flood fill from (`x`, `y`) over the pixels `inside` allows. Instead of visiting
+/// pixels one by one, whole horizontal runs are filled at once and only the start of every run
+/// in the rows above and below is pushed on an explicit stack.
+fn scanline_fill(
+    width: u32,
+    height: u32,
+    (x, y): (u32, u32),
+    connectivity: Connectivity,
+    inside: impl Fn(usize) -> bool,
+) -> Vec<bool> {
+    let (width, height) = (width as usize, height as usize);
+    // how far past the ends of a run the rows above and below still touch it
+    let reach = match connectivity {
+        Connectivity::Four => 0,
+        Connectivity::Eight => 1,
+    };
+
+    let mut filled = vec![false; width * height];
+    let fillable = |filled: &[bool], x: usize, y: usize| {
+        let index = y * width + x;
+        !filled[index] && inside(index)
+    };
+
+    let mut stack = vec![(x as usize, y as usize)];
+    while let Some((x, y)) = stack.pop() {
+        if !fillable(&filled, x, y) {
+            continue;
+        }
+
+        let mut left = x;
+        while left > 0 && fillable(&filled, left - 1, y) {
+            left -= 1;
+        }
+        let mut right = x;
+        while right + 1 < width && fillable(&filled, right + 1, y) {
+            right += 1;
+        }
+        filled[y * width + left..=y * width + right].fill(true);
+
+        let from = left.saturating_sub(reach);
+        let to = (right + reach).min(width - 1);
+        let rows = [y.checked_sub(1), Some(y + 1).filter(|y| *y < height)];
+
+        for row in rows.into_iter().flatten() {
+            let mut in_run = false;
+            for x in from..=to {
+                let fill = fillable(&filled, x, row);
+                if fill && !in_run {
+                    stack.push((x, row));
+                }
+                in_run = fill;
+            }
+        }
+    }
+
+    filled
+}
+
+impl CanvasImage {
+    /// Select the region around (`x`, `y`) whose colors are within `tolerance` of the color at
+    /// (`x`, `y`), pixels only touching diagonally are not part of it
+    ///
+    /// # Arguments
+    /// * tolerance: largest distance to the clicked color, in the units of `color_distance`
+    /// # Returns
+    ///  the selection mask, row by row
+    pub fn magic_wand(
+        &self,
+        x: u32,
+        y: u32,
+        tolerance: f64,
+        color_distance: ColorDistance,
+    ) -> Vec<bool> {
+        self.select(x, y, tolerance, color_distance, Connectivity::Four)
+    }
+
+    /// Paint bucket: fill the region around (`x`, `y`) whose colors are within `tolerance` (as
+    /// RGB distance) of the color at (`x`, `y`) with `color`
+    pub fn flood_fill(
+        &self,
+        x: u32,
+        y: u32,
+        color: (u8, u8, u8, u8),
+        tolerance: f64,
+        connectivity: Connectivity,
+    ) -> CanvasImage {
+        let selection = self.select(x, y, tolerance, ColorDistance::Rgb, connectivity);
+
+        let (r, g, b, a) = color;
+        let mut filled =
+            CanvasImage::from_vec_with_size(self.data.clone(), self.width, self.height);
+        for (pixel, selected) in filled.data.chunks_exact_mut(4).zip(selection) {
+            if selected {
+                pixel.copy_from_slice(&[r, g, b, a]);
+            }
+        }
+
+        filled
+    }
+
+    fn select(
+        &self,
+        x: u32,
+        y: u32,
+        tolerance: f64,
+        color_distance: ColorDistance,
+        connectivity: Connectivity,
+    ) -> Vec<bool> {
+        assert!(
+            x < self.horizontal_size() && y < self.vertical_size(),
+            "the seed must be inside the image"
+        );
+        let seed = self.rgba(x, y).unwrap();
+        let distances = color_distance.distances(self, seed);
+
+        scanline_fill(self.width, self.height, (x, y), connectivity, |i| {
+            distances[i] <= tolerance
+        })
+    }
+}
+
+/// Error for the wasm exports when (`x`, `y`) is not a pixel of `image`
+pub(super) fn check_point(image: &CanvasImage, x: u32, y: u32) -> Result<(), String> {
+    if x < image.horizontal_size() && y < image.vertical_size() {
+        Ok(())
+    } else {
+        Err(format!(
+            "({x}, {y}) is outside of the {} x {} image",
+            image.horizontal_size(),
+            image.vertical_size()
+        ))
+    }
+}
+
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn flood_fill(
+    image: ImageData,
+    x: u32,
+    y: u32,
+    r: u8,
+    g: u8,
+    b: u8,
+    a: u8,
+    tolerance: f64,
+    connectivity: Connectivity,
+) -> Result<ImageData, String> {
+    let image = CanvasImage::from_image_data(image);
+    check_point(&image, x, y)?;
+    Ok(image
+        .flood_fill(x, y, (r, g, b, a), tolerance, connectivity)
+        .into())
+}
+
+/// # Returns
+///  the selection as a mask, selected pixels are white and the rest is black
+#[wasm_bindgen]
+pub fn magic_wand(
+    image: ImageData,
+    x: u32,
+    y: u32,
+    tolerance: f64,
+    color_distance: ColorDistance,
+) -> Result<ImageData, String> {
+    let image = CanvasImage::from_image_data(image);
+    check_point(&image, x, y)?;
+    let selection = image.magic_wand(x, y, tolerance, color_distance);
+    Ok(
+        CanvasImage::from_foreground(selection, image.horizontal_size(), image.vertical_size())
+            .into(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Rows of `#` (black) and `.` (white), `o` is a white pixel that is off by a little
+    fn picture(rows: &[&str]) -> CanvasImage {
+        let rgba = rows
+            .iter()
+            .flat_map(|row| row.chars())
+            .flat_map(|c| match c {
+                '#' => [0, 0, 0, 255],
+                'o' => [250, 245, 255, 255],
+                _ => [255, 255, 255, 255],
+            });
+        CanvasImage::from_vec_with_size(rgba.collect(), rows[0].len() as u32, rows.len() as u32)
+    }
+
+    #[rustfmt::skip]
+    const WALLS: [&str; 6] = [
+        "..#.....",
+        "..#.###.",
+        "..#.#o#.",
+        "##..#.#.",
+        "....###.",
+        "#.......",
+    ];
+
+    #[test]
+    fn fill_stops_at_walls() {
+        let image = picture(&WALLS);
+
+        // the top left corner is walled in, unless the fill can squeeze through the diagonal
+        let four = image.flood_fill(0, 0, (255, 0, 0, 255), 0.0, Connectivity::Four);
+        let red = |image: &CanvasImage| {
+            iproduct!(0..6, 0..8)
+                .filter(|(y, x)| image.rgba(*x, *y) == Some((255, 0, 0, 255)))
+                .count()
+        };
+        assert_eq!(red(&four), 6);
+
+        let eight = image.flood_fill(0, 0, (255, 0, 0, 255), 0.0, Connectivity::Eight);
+        assert_eq!(eight.rgba(3, 3), Some((255, 0, 0, 255)));
+        assert_eq!(eight.rgba(5, 3), Some((255, 255, 255, 255)));
+    }
+
+    #[test]
+    fn wand_tolerance() {
+        let image = picture(&WALLS);
+        let selected = |mask: &[bool], x: usize, y: usize| mask[y * 8 + x];
+
+        // the slightly off pixel breaks the run unless the tolerance covers it
+        let strict = image.magic_wand(5, 3, 0.0, ColorDistance::Rgb);
+        assert!(!selected(&strict, 5, 2));
+        assert_eq!(strict.iter().filter(|s| **s).count(), 1);
+
+        let loose = image.magic_wand(5, 3, 12.0, ColorDistance::Rgb);
+        assert!(selected(&loose, 5, 2));
+
+        let lab = image.magic_wand(5, 3, 7.0, ColorDistance::Lab);
+        assert!(selected(&lab, 5, 2));
+        assert!(!selected(&lab, 4, 2));
+    }
+
+    #[test]
+    #[should_panic(expected = "the seed must be inside the image")]
+    fn seed_past_the_right_edge() {
+        // (8, 0) is not in the image even though the same index is (0, 1)
+        picture(&WALLS).magic_wand(8, 0, 0.0, ColorDistance::Rgb);
+    }
+}
@@ -0,0 +1,133 @@
+use super::*;
+use crate::color_space::{to_srgb, Linearize};
+
+impl CanvasImage {
+    /// Sharpen the image by adding back the difference between the image and a Gaussian blurred
+    /// copy of it, the same way the unsharp mask in every photo editor works. Alpha is left
+    /// untouched.
+    ///
+    /// # Arguments
+    /// * radius: the standard deviation of the Gaussian blur in pixels, larger values sharpen
+    ///   coarser details
+    /// * amount: how much of the difference gets added back, 1.0 adds all of it
+    /// * threshold: the smallest difference (in [0, 1]) that is sharpened at all, anything below
+    ///   it is left alone so flat areas don't pick up noise
+    pub fn unsharp_mask(&self, radius: f64, amount: f64, threshold: f64) -> CanvasImage {
+        self.sharpen(radius, amount, threshold, false)
+    }
+
+    /// Same as `unsharp_mask`, but the difference is computed on linear RGB instead of the sRGB
+    /// encoded values, which avoids dark halos around bright edges
+    pub fn unsharp_mask_in_linear_light(
+        &self,
+        radius: f64,
+        amount: f64,
+        threshold: f64,
+    ) -> CanvasImage {
+        self.sharpen(radius, amount, threshold, true)
+    }
+
+    /// High-boost filtering, `f + k * (f - blurred)`. k = 1 is a plain unsharp mask and anything
+    /// above boosts the high frequencies even further.
+    pub fn high_boost(&self, k: f64) -> CanvasImage {
+        self.sharpen(1.0, k, 0.0, false)
+    }
+
+    fn sharpen(&self, radius: f64, amount: f64, threshold: f64, linear_light: bool) -> CanvasImage {
+        let mut sharpened =
+            CanvasImage::from_vec_with_size(self.data.clone(), self.width, self.height);
+
+        for channel in Channel::COLOR {
+            let mut plane = self.plane(channel);
+            if linear_light {
+                plane = plane.map(|v| v.linearize());
+            }
+
+            let blurred = plane.gaussian_blur(radius);
+
+            let mut plane = plane.zip_map(&blurred, |original, blurred| {
+                let difference = original - blurred;
+
+                if difference.abs() <= threshold {
+                    original
+                } else {
+                    original + amount * difference
+                }
+            });
+
+            if linear_light {
+                plane = plane.map(|v| to_srgb(v.clamp(0.0, 1.0)));
+            }
+
+            sharpened.set_plane(channel, &plane);
+        }
+
+        sharpened
+    }
+}
+
+#[wasm_bindgen]
+pub fn unsharp_mask(
+    image: ImageData,
+    radius: f64,
+    amount: f64,
+    threshold: f64,
+    linear_light: bool,
+) -> Result<ImageData, String> {
+    if radius <= 0.0 {
+        return Err(format!("radius must be positive, got {radius}"));
+    }
+
+    let image = CanvasImage::from_image_data(image);
+
+    let sharpened = if linear_light {
+        image.unsharp_mask_in_linear_light(radius, amount, threshold)
+    } else {
+        image.unsharp_mask(radius, amount, threshold)
+    };
+    Ok(sharpened.into())
+}
+
+#[wasm_bindgen]
+pub fn high_boost(image: ImageData, k: f64) -> ImageData {
+    let image = CanvasImage::from_image_data(image);
+    image.high_boost(k).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 16 x 4 image, dark on the left half and bright on the right half
+    fn step_edge() -> CanvasImage {
+        let rgba = iproduct!(0..4, 0..16).flat_map(|(_, x)| {
+            let v = if x < 8 { 64 } else { 192 };
+            [v, v, v, 255]
+        });
+
+        CanvasImage::from_vec_with_size(Vec::from_iter(rgba), 16, 4)
+    }
+
+    #[test]
+    fn unsharp_mask_overshoots_edges() {
+        let image = step_edge();
+        let sharpened = image.unsharp_mask(1.0, 1.0, 0.0);
+
+        // the dark side of the edge gets darker and the bright side brighter
+        assert!(sharpened.r(7, 2).unwrap() < 64);
+        assert!(sharpened.r(8, 2).unwrap() > 192);
+
+        // far away from the edge nothing changes
+        assert_eq!(sharpened.r(0, 2), Some(64));
+        assert_eq!(sharpened.r(15, 2), Some(192));
+        assert_eq!(sharpened.a(8, 2), Some(255));
+    }
+
+    #[test]
+    fn threshold_protects_small_differences() {
+        let image = step_edge();
+        let sharpened = image.unsharp_mask(1.0, 1.0, 1.0);
+
+        assert_eq!(sharpened.rgba_slice(), image.rgba_slice());
+    }
+}
@@ -0,0 +1,406 @@
+use super::*;
+use crate::float_image::IntegralImage;
+
+/// How many grid cells of padding the bilateral grid keeps on each side, so the blur and the
+/// trilinear slicing never have to worry about the borders
+const GRID_PADDING: usize = 2;
+
+impl CanvasImage {
+    /// Smooth the image while keeping edges intact, each pixel becomes the average of its
+    /// neighbours weighted by both how far away they are and how different their color is.
+    /// Alpha is left untouched.
+    ///
+    /// This is the exact version, the cost grows with the square of `spatial_sigma`, for large
+    /// values use `fast_bilateral_filter` instead.
+    ///
+    /// # Arguments
+    /// * spatial_sigma: standard deviation of the spatial Gaussian, in pixels
+    /// * range_sigma: standard deviation of the range Gaussian, in [0, 1] intensity units
+    pub fn bilateral_filter(&self, spatial_sigma: f64, range_sigma: f64) -> CanvasImage {
+        assert!(
+            spatial_sigma > 0.0 && range_sigma > 0.0,
+            "sigma must be positive"
+        );
+
+        let radius = (2.0 * spatial_sigma).ceil() as i64;
+        let planes = Channel::COLOR.map(|channel| self.plane(channel));
+
+        let offsets: Vec<_> = iproduct!(-radius..=radius, -radius..=radius).collect();
+        let spatial_weights: Vec<_> = offsets
+            .iter()
+            .map(|(dy, dx)| {
+                let d2 = (dx * dx + dy * dy) as f64;
+                (-d2 / (2.0 * spatial_sigma * spatial_sigma)).exp()
+            })
+            .collect();
+        let range_factor = -1.0 / (2.0 * range_sigma * range_sigma);
+
+        let mut filtered =
+            CanvasImage::from_vec_with_size(self.data.clone(), self.width, self.height);
+        let mut outputs = Channel::COLOR.map(|_| FloatImage::new(self.width, self.height));
+
+        for (y, x) in iproduct!(0..self.height, 0..self.width) {
+            let center = planes.each_ref().map(|plane| plane.get(x, y).unwrap());
+
+            let mut acc = [0f64; 3];
+            let mut weight_sum = 0f64;
+
+            for ((dy, dx), spatial_weight) in offsets.iter().zip(spatial_weights.iter()) {
+                let neighbour = planes
+                    .each_ref()
+                    .map(|plane| plane.reflective(x as i64 + dx, y as i64 + dy));
+
+                let color_distance: f64 = Iterator::zip(neighbour.iter(), center.iter())
+                    .map(|(n, c)| (n - c) * (n - c))
+                    .sum();
+
+                let weight = spatial_weight * (color_distance * range_factor).exp();
+                acc.iter_mut()
+                    .zip(neighbour.iter())
+                    .for_each(|(acc, n)| *acc += weight * n);
+                weight_sum += weight;
+            }
+
+            for (output, acc) in outputs.iter_mut().zip(acc.iter()) {
+                output.set(x, y, acc / weight_sum);
+            }
+        }
+
+        for (channel, output) in Channel::COLOR.iter().zip(outputs.iter()) {
+            filtered.set_plane(*channel, output);
+        }
+
+        filtered
+    }
+
+    /// Approximate bilateral filter using a bilateral grid (Paris and Durand), the image is
+    /// splatted into a coarse (x, y, intensity) grid, blurred there and sliced back out. The cost
+    /// barely depends on `spatial_sigma`, so this is the one to use for large radii. Each color
+    /// channel is filtered on its own.
+    pub fn fast_bilateral_filter(&self, spatial_sigma: f64, range_sigma: f64) -> CanvasImage {
+        assert!(
+            spatial_sigma > 0.0 && range_sigma > 0.0,
+            "sigma must be positive"
+        );
+
+        let mut filtered =
+            CanvasImage::from_vec_with_size(self.data.clone(), self.width, self.height);
+
+        for channel in Channel::COLOR {
+            let plane = self.plane(channel);
+            let smoothed = bilateral_grid(&plane, spatial_sigma, range_sigma);
+            filtered.set_plane(channel, &smoothed);
+        }
+
+        filtered
+    }
+
+    /// Guided filter (He et al.), smooths the image while following the edges of `guide`. When
+    /// the guide is the image itself this is an edge preserving smoother similar to the bilateral
+    /// filter but in linear time. The luminance of the guide is used.
+    ///
+    /// # Arguments
+    /// * radius: radius of the square window the local linear models are fitted over
+    /// * eps: regularization, edges with a variance well below `eps` get smoothed away
+    pub fn guided_filter(&self, guide: &CanvasImage, radius: u32, eps: f64) -> CanvasImage {
+        assert_eq!(guide.width, self.width, "guide must be the same size");
+        assert_eq!(guide.height, self.height, "guide must be the same size");
+
+        let guide = guide.luminance();
+        let mean_guide = guide.box_mean(radius);
+        let variance_guide = guide
+            .map(|v| v * v)
+            .box_mean(radius)
+            .zip_map(&mean_guide, |corr, mean| corr - mean * mean);
+
+        let mut filtered =
+            CanvasImage::from_vec_with_size(self.data.clone(), self.width, self.height);
+
+        for channel in Channel::COLOR {
+            let input = self.plane(channel);
+            let mean_input = input.box_mean(radius);
+            let covariance = guide
+                .zip_map(&input, |g, p| g * p)
+                .box_mean(radius)
+                .zip_map(
+                    &mean_guide.zip_map(&mean_input, |g, p| g * p),
+                    |corr, mean| corr - mean,
+                );
+
+            let a = covariance.zip_map(&variance_guide, |cov, var| cov / (var + eps));
+            let b = mean_input.zip_map(&a.zip_map(&mean_guide, |a, g| a * g), |p, ag| p - ag);
+
+            let output = a
+                .box_mean(radius)
+                .zip_map(&guide, |a, g| a * g)
+                .zip_map(&b.box_mean(radius), |ag, b| ag + b);
+
+            filtered.set_plane(channel, &output);
+        }
+
+        filtered
+    }
+
+    /// Kuwahara filter, looks at the four (radius + 1) x (radius + 1) quadrants that share the
+    /// pixel as a corner and takes the mean color of the one with the lowest luminance variance.
+    /// Gives a painterly look while keeping edges crisp.
+    pub fn kuwahara(&self, radius: u32) -> CanvasImage {
+        let r = radius as i64;
+        let luminance = self.luminance();
+        let luminance_sum = IntegralImage::new(&luminance);
+        let luminance_squared_sum = IntegralImage::new(&luminance.map(|v| v * v));
+        let color_sums = Channel::COLOR.map(|channel| IntegralImage::new(&self.plane(channel)));
+
+        let mut filtered =
+            CanvasImage::from_vec_with_size(self.data.clone(), self.width, self.height);
+        let mut outputs = Channel::COLOR.map(|_| FloatImage::new(self.width, self.height));
+
+        for (y, x) in iproduct!(0..self.height, 0..self.width) {
+            let (x, y) = (x as i64, y as i64);
+            let quadrants = [
+                (x - r, y - r, x, y),
+                (x, y - r, x + r, y),
+                (x - r, y, x, y + r),
+                (x, y, x + r, y + r),
+            ];
+
+            let variance = |&(x0, y0, x1, y1): &(i64, i64, i64, i64)| {
+                let mean = luminance_sum.mean(x0, y0, x1, y1);
+                luminance_squared_sum.mean(x0, y0, x1, y1) - mean * mean
+            };
+
+            let (x0, y0, x1, y1) = quadrants
+                .iter()
+                .min_by(|a, b| variance(a).total_cmp(&variance(b)))
+                .copied()
+                .unwrap();
+
+            for (output, sums) in outputs.iter_mut().zip(color_sums.iter()) {
+                output.set(x as u32, y as u32, sums.mean(x0, y0, x1, y1));
+            }
+        }
+
+        for (channel, output) in Channel::COLOR.iter().zip(outputs.iter()) {
+            filtered.set_plane(*channel, output);
+        }
+
+        filtered
+    }
+}
+
+/// Bilateral filter a single plane with a bilateral grid, see `fast_bilateral_filter`
+fn bilateral_grid(plane: &FloatImage, spatial_sigma: f64, range_sigma: f64) -> FloatImage {
+    let width = plane.horizontal_size();
+    let height = plane.vertical_size();
+    if width == 0 || height == 0 {
+        return FloatImage::new(width, height);
+    }
+
+    let grid_width = ((width - 1) as f64 / spatial_sigma).ceil() as usize + 1 + 2 * GRID_PADDING;
+    let grid_height = ((height - 1) as f64 / spatial_sigma).ceil() as usize + 1 + 2 * GRID_PADDING;
+    let grid_depth = (1.0 / range_sigma).ceil() as usize + 1 + 2 * GRID_PADDING;
+    let dimensions = [grid_width, grid_height, grid_depth];
+
+    let index = |gx: usize, gy: usize, gz: usize| (gz * grid_height + gy) * grid_width + gx;
+
+    // splat, every pixel lands in the nearest grid cell
+    let mut values = vec![0f64; grid_width * grid_height * grid_depth];
+    let mut weights = vec![0f64; grid_width * grid_height * grid_depth];
+    for (y, x) in iproduct!(0..height, 0..width) {
+        let v = plane.get(x, y).unwrap();
+
+        let gx = (x as f64 / spatial_sigma).round() as usize + GRID_PADDING;
+        let gy = (y as f64 / spatial_sigma).round() as usize + GRID_PADDING;
+        let gz = (v.clamp(0.0, 1.0) / range_sigma).round() as usize + GRID_PADDING;
+
+        values[index(gx, gy, gz)] += v;
+        weights[index(gx, gy, gz)] += 1.0;
+    }
+
+    // blur, one grid cell corresponds to one sigma in every direction
+    for axis in 0..3 {
+        values = blur_grid_axis(&values, dimensions, axis);
+        weights = blur_grid_axis(&weights, dimensions, axis);
+    }
+
+    // slice, read the grid back out at the position of every pixel
+    FloatImage::from_fn(width, height, |x, y| {
+        let v = plane.get(x, y).unwrap();
+        let position = [
+            x as f64 / spatial_sigma + GRID_PADDING as f64,
+            y as f64 / spatial_sigma + GRID_PADDING as f64,
+            v.clamp(0.0, 1.0) / range_sigma + GRID_PADDING as f64,
+        ];
+
+        let base = position.map(|p| p.floor() as usize);
+        let t = [0, 1, 2].map(|i| position[i] - base[i] as f64);
+
+        let mut value = 0f64;
+        let mut weight = 0f64;
+        for (dz, dy, dx) in iproduct!(0..2, 0..2, 0..2) {
+            let w = (if dx == 0 { 1.0 - t[0] } else { t[0] })
+                * (if dy == 0 { 1.0 - t[1] } else { t[1] })
+                * (if dz == 0 { 1.0 - t[2] } else { t[2] });
+
+            let i = index(base[0] + dx, base[1] + dy, base[2] + dz);
+            value += w * values[i];
+            weight += w * weights[i];
+        }
+
+        if weight > f64::EPSILON {
+            value / weight
+        } else {
+            v
+        }
+    })
+}
+
+/// Convolve the grid along one axis with the binomial approximation of a Gaussian with a
+/// standard deviation of 1 cell, cells outside of the grid count as empty
+fn blur_grid_axis(grid: &[f64], dimensions: [usize; 3], axis: usize) -> Vec<f64> {
+    const WEIGHTS: [f64; 5] = [1.0 / 16.0, 4.0 / 16.0, 6.0 / 16.0, 4.0 / 16.0, 1.0 / 16.0];
+
+    let [width, height, depth] = dimensions;
+    let stride = [1, width, width * height][axis];
+    let length = dimensions[axis];
+
+    let mut blurred = vec![0f64; grid.len()];
+    for (z, y, x) in iproduct!(0..depth, 0..height, 0..width) {
+        let i = (z * height + y) * width + x;
+        let position = [x, y, z][axis] as isize;
+
+        blurred[i] = WEIGHTS
+            .iter()
+            .enumerate()
+            .map(|(k, w)| (position + k as isize - 2, w))
+            .filter(|(p, _)| *p >= 0 && (*p as usize) < length)
+            .map(|(p, w)| w * grid[(i as isize + (p - position) * stride as isize) as usize])
+            .sum();
+    }
+
+    blurred
+}
+
+/// Both sigmas of the bilateral filters have to be positive, anything else divides by zero
+fn check_sigmas(spatial_sigma: f64, range_sigma: f64) -> Result<(), String> {
+    if spatial_sigma > 0.0 && range_sigma > 0.0 {
+        Ok(())
+    } else {
+        Err(format!(
+            "sigma must be positive, got {spatial_sigma} and {range_sigma}"
+        ))
+    }
+}
+
+#[wasm_bindgen]
+pub fn bilateral_filter(
+    image: ImageData,
+    spatial_sigma: f64,
+    range_sigma: f64,
+) -> Result<ImageData, String> {
+    check_sigmas(spatial_sigma, range_sigma)?;
+    let image = CanvasImage::from_image_data(image);
+    Ok(image.bilateral_filter(spatial_sigma, range_sigma).into())
+}
+
+#[wasm_bindgen]
+pub fn fast_bilateral_filter(
+    image: ImageData,
+    spatial_sigma: f64,
+    range_sigma: f64,
+) -> Result<ImageData, String> {
+    check_sigmas(spatial_sigma, range_sigma)?;
+    let image = CanvasImage::from_image_data(image);
+    Ok(image
+        .fast_bilateral_filter(spatial_sigma, range_sigma)
+        .into())
+}
+
+#[wasm_bindgen]
+pub fn guided_filter(
+    image: ImageData,
+    guide: ImageData,
+    radius: u32,
+    eps: f64,
+) -> Result<ImageData, String> {
+    if eps <= 0.0 {
+        return Err(format!("eps must be positive, got {eps}"));
+    }
+
+    let image = CanvasImage::from_image_data(image);
+    let guide = CanvasImage::from_image_data(guide);
+    if (image.width, image.height) != (guide.width, guide.height) {
+        return Err("the image and the guide must be the same size".to_string());
+    }
+
+    Ok(image.guided_filter(&guide, radius, eps).into())
+}
+
+#[wasm_bindgen]
+pub fn kuwahara(image: ImageData, radius: u32) -> ImageData {
+    let image = CanvasImage::from_image_data(image);
+    image.kuwahara(radius).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas_image::test_util::noisy_step_edge;
+
+    fn assert_smoothed_but_sharp(filtered: &CanvasImage) {
+        for y in 0..12 {
+            // the noise is (mostly) gone
+            assert!(filtered.r(2, y).unwrap().abs_diff(60) <= 2);
+            assert!(filtered.r(9, y).unwrap().abs_diff(200) <= 2);
+
+            // but the edge stays where it was
+            assert!(filtered.r(5, y).unwrap() < 80);
+            assert!(filtered.r(6, y).unwrap() > 180);
+        }
+    }
+
+    #[test]
+    fn bilateral_keeps_edges() {
+        let filtered = noisy_step_edge(12, 4).bilateral_filter(2.0, 0.1);
+        assert_smoothed_but_sharp(&filtered);
+    }
+
+    #[test]
+    fn fast_bilateral_keeps_edges() {
+        let filtered = noisy_step_edge(12, 4).fast_bilateral_filter(2.0, 0.1);
+        assert_smoothed_but_sharp(&filtered);
+    }
+
+    #[test]
+    fn empty_image() {
+        let empty = CanvasImage::from_vec_with_size(Vec::new(), 0, 3);
+        assert!(empty
+            .fast_bilateral_filter(2.0, 0.1)
+            .rgba_slice()
+            .is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "sigma must be positive")]
+    fn zero_range_sigma_is_rejected() {
+        noisy_step_edge(12, 4).fast_bilateral_filter(2.0, 0.0);
+    }
+
+    #[test]
+    fn guided_filter_keeps_edges() {
+        let image = noisy_step_edge(12, 4);
+        let filtered = image.guided_filter(&image, 2, 0.01);
+        assert_smoothed_but_sharp(&filtered);
+    }
+
+    #[test]
+    fn kuwahara_keeps_edges() {
+        let filtered = noisy_step_edge(12, 4).kuwahara(2);
+
+        for y in 0..12 {
+            assert!(filtered.r(5, y).unwrap() < 80);
+            assert!(filtered.r(6, y).unwrap() > 180);
+            assert_eq!(filtered.a(5, y), Some(255));
+        }
+    }
+}
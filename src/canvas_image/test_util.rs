@@ -0,0 +1,41 @@
+//! Test images shared by the tests of the submodules
+use super::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// A 20 x 20 black image with a white 10 x 10 square in the middle, covering 5 to 14 in both
+/// directions
+pub fn white_square() -> CanvasImage {
+    let rgba = iproduct!(0..20, 0..20).flat_map(|(y, x)| {
+        let v = if (5..15).contains(&x) && (5..15).contains(&y) {
+            255
+        } else {
+            0
+        };
+        [v, v, v, 255]
+    });
+
+    CanvasImage::from_vec_with_size(rgba.collect(), 20, 20)
+}
+
+/// Build a binary image out of rows of `#` (foreground) and `.` (background)
+pub fn binary(rows: &[&str]) -> CanvasImage {
+    let mask = rows
+        .iter()
+        .flat_map(|row| row.chars().map(|c| c == '#'))
+        .collect();
+    CanvasImage::from_foreground(mask, rows[0].len() as u32, rows.len() as u32)
+}
+
+/// A `size` x `size` image, 60 on the left half and 200 on the right half, with uniform noise of
+/// up to +-`noise` on top. The noise is seeded so every call gives the same image.
+pub fn noisy_step_edge(size: u32, noise: i32) -> CanvasImage {
+    let mut rng = StdRng::seed_from_u64(42);
+    let rgba = iproduct!(0..size, 0..size).flat_map(|(_, x)| {
+        let base = if x < size / 2 { 60 } else { 200 };
+        let v = (base + rng.gen_range(-noise..=noise)) as u8;
+        [v, v, v, 255]
+    });
+
+    CanvasImage::from_vec_with_size(rgba.collect(), size, size)
+}
@@ -0,0 +1,286 @@
+use super::*;
+
+/// Levels adjustment: `in_black` and everything below maps to `out_black`, `in_white` and
+/// everything above to `out_white`, and the range in between is bent by `gamma`. All levels are
+/// in [0, 255], a gamma above 1 brightens the midtones.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Levels {
+    pub in_black: f64,
+    pub in_white: f64,
+    pub gamma: f64,
+    pub out_black: f64,
+    pub out_white: f64,
+}
+
+impl Levels {
+    pub fn apply(&self, value: f64) -> f64 {
+        let range = (self.in_white - self.in_black).max(f64::EPSILON);
+        let t = ((value - self.in_black) / range).clamp(0.0, 1.0);
+        let t = t.powf(1.0 / self.gamma);
+
+        self.out_black + t * (self.out_white - self.out_black)
+    }
+
+    pub fn table(&self) -> [u8; 256] {
+        table(|v| self.apply(v))
+    }
+}
+
+/// Smooth tone curve through control points, a monotone cubic (Fritsch-Carlson) spline so it
+/// never overshoots between the points. Left of the first point and right of the last it stays
+/// flat.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Curve {
+    points: Vec<(f64, f64)>,
+    tangents: Vec<f64>,
+}
+
+impl Curve {
+    /// The curve through `points`, (input, output) pairs in [0, 255] in any order. Without any
+    /// points the curve is the identity.
+    pub fn new(points: &[(f64, f64)]) -> Curve {
+        let mut points = points.to_vec();
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        points.dedup_by(|a, b| a.0 == b.0);
+        if points.is_empty() {
+            points = vec![(0.0, 0.0), (255.0, 255.0)];
+        }
+
+        let secants: Vec<f64> = points
+            .windows(2)
+            .map(|p| (p[1].1 - p[0].1) / (p[1].0 - p[0].0))
+            .collect();
+
+        let n = points.len();
+        let mut tangents = vec![0.0; n];
+        if n > 1 {
+            tangents[0] = secants[0];
+            tangents[n - 1] = secants[n - 2];
+        }
+        for k in 1..n.saturating_sub(1) {
+            let (before, after) = (secants[k - 1], secants[k]);
+            tangents[k] = if before * after <= 0.0 {
+                0.0
+            } else {
+                (before + after) / 2.0
+            };
+        }
+
+        // limit the tangents so every segment stays monotone
+        for (k, secant) in secants.iter().enumerate() {
+            if *secant == 0.0 {
+                tangents[k] = 0.0;
+                tangents[k + 1] = 0.0;
+                continue;
+            }
+
+            let (a, b) = (tangents[k] / secant, tangents[k + 1] / secant);
+            let length = f64::hypot(a, b);
+            if length > 3.0 {
+                tangents[k] = 3.0 / length * a * secant;
+                tangents[k + 1] = 3.0 / length * b * secant;
+            }
+        }
+
+        Curve { points, tangents }
+    }
+
+    pub fn apply(&self, x: f64) -> f64 {
+        let (first, last) = (self.points[0], self.points[self.points.len() - 1]);
+        if x <= first.0 {
+            return first.1;
+        }
+        if x >= last.0 {
+            return last.1;
+        }
+
+        let k = self.points.partition_point(|p| p.0 <= x) - 1;
+        let ((x0, y0), (x1, y1)) = (self.points[k], self.points[k + 1]);
+        let h = x1 - x0;
+        let t = (x - x0) / h;
+
+        // cubic Hermite basis
+        let t2 = t * t;
+        let t3 = t2 * t;
+        (2.0 * t3 - 3.0 * t2 + 1.0) * y0
+            + (t3 - 2.0 * t2 + t) * h * self.tangents[k]
+            + (-2.0 * t3 + 3.0 * t2) * y1
+            + (t3 - t2) * h * self.tangents[k + 1]
+    }
+
+    pub fn table(&self) -> [u8; 256] {
+        table(|v| self.apply(v))
+    }
+}
+
+/// `f` for every 8 bit value, rounded and clamped back to 8 bits
+fn table(f: impl Fn(f64) -> f64) -> [u8; 256] {
+    let mut table = [0; 256];
+    for (v, entry) in table.iter_mut().enumerate() {
+        *entry = f(v as f64).round().clamp(0.0, 255.0) as u8;
+    }
+
+    table
+}
+
+/// Pairs up `[x0, y0, x1, y1, ...]`
+fn control_points(flat: &[f64]) -> Vec<(f64, f64)> {
+    flat.chunks_exact(2).map(|p| (p[0], p[1])).collect()
+}
+
+impl CanvasImage {
+    /// Look every value of `channels` up in `table`
+    pub(super) fn apply_table(&self, channels: &[Channel], table: &[u8; 256]) -> CanvasImage {
+        let mut data = self.data.clone();
+        for pixel in data.chunks_exact_mut(4) {
+            for channel in channels {
+                let value = &mut pixel[channel.offset()];
+                *value = table[*value as usize];
+            }
+        }
+
+        CanvasImage::from_vec_with_size(data, self.width, self.height)
+    }
+
+    /// Levels on the red, green and blue channels alike, see `Levels`
+    pub fn levels(
+        &self,
+        in_black: f64,
+        in_white: f64,
+        gamma: f64,
+        out_black: f64,
+        out_white: f64,
+    ) -> CanvasImage {
+        let levels = Levels {
+            in_black,
+            in_white,
+            gamma,
+            out_black,
+            out_white,
+        };
+        self.apply_table(&Channel::COLOR, &levels.table())
+    }
+
+    /// Levels on a single channel, e.g. to fix a color cast
+    pub fn channel_levels(&self, channel: Channel, levels: Levels) -> CanvasImage {
+        self.apply_table(&[channel], &levels.table())
+    }
+
+    /// Tone curve through `points` on the red, green and blue channels alike, see `Curve`
+    pub fn curves(&self, points: &[(f64, f64)]) -> CanvasImage {
+        self.apply_table(&Channel::COLOR, &Curve::new(points).table())
+    }
+
+    /// Tone curve through `points` on a single channel
+    pub fn channel_curves(&self, channel: Channel, points: &[(f64, f64)]) -> CanvasImage {
+        self.apply_table(&[channel], &Curve::new(points).table())
+    }
+}
+
+#[wasm_bindgen]
+pub fn levels(
+    image: ImageData,
+    in_black: f64,
+    in_white: f64,
+    gamma: f64,
+    out_black: f64,
+    out_white: f64,
+) -> ImageData {
+    let image = CanvasImage::from_image_data(image);
+    image
+        .levels(in_black, in_white, gamma, out_black, out_white)
+        .into()
+}
+
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn channel_levels(
+    image: ImageData,
+    channel: Channel,
+    in_black: f64,
+    in_white: f64,
+    gamma: f64,
+    out_black: f64,
+    out_white: f64,
+) -> ImageData {
+    let image = CanvasImage::from_image_data(image);
+    let levels = Levels {
+        in_black,
+        in_white,
+        gamma,
+        out_black,
+        out_white,
+    };
+    image.channel_levels(channel, levels).into()
+}
+
+/// # Arguments
+/// * points: the control points as `[x0, y0, x1, y1, ...]`, all in [0, 255]
+#[wasm_bindgen]
+pub fn curves(image: ImageData, points: &[f64]) -> ImageData {
+    let image = CanvasImage::from_image_data(image);
+    image.curves(&control_points(points)).into()
+}
+
+/// # Arguments
+/// * points: the control points as `[x0, y0, x1, y1, ...]`, all in [0, 255]
+#[wasm_bindgen]
+pub fn channel_curves(image: ImageData, channel: Channel, points: &[f64]) -> ImageData {
+    let image = CanvasImage::from_image_data(image);
+    image
+        .channel_curves(channel, &control_points(points))
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_stretch_and_bend() {
+        let rgba = vec![10, 128, 250, 100, 60, 60, 60, 255];
+        let image = CanvasImage::from_vec_with_size(rgba, 2, 1);
+
+        let stretched = image.levels(10.0, 250.0, 1.0, 0.0, 255.0);
+        assert_eq!(stretched.rgba(0, 0), Some((0, 125, 255, 100)));
+
+        // brighter midtones, the ends stay put
+        let bright = Levels {
+            in_black: 0.0,
+            in_white: 255.0,
+            gamma: 2.0,
+            out_black: 0.0,
+            out_white: 255.0,
+        };
+        assert_eq!(bright.apply(0.0), 0.0);
+        assert_eq!(bright.apply(255.0), 255.0);
+        assert!(bright.apply(64.0) > 120.0);
+
+        let blue = image.channel_levels(Channel::Blue, bright);
+        assert_eq!(blue.rgba(1, 0), Some((60, 60, 124, 255)));
+    }
+
+    #[test]
+    fn curves_are_monotone() {
+        // the identity without points or with the end points only
+        assert!((0..256).all(|v| Curve::new(&[]).table()[v] == v as u8));
+        let line = Curve::new(&[(255.0, 255.0), (0.0, 0.0)]);
+        assert!((0..256).all(|v| line.table()[v] == v as u8));
+
+        // an S curve goes through its points and never turns back
+        let s = Curve::new(&[(0.0, 0.0), (64.0, 40.0), (192.0, 220.0), (255.0, 255.0)]);
+        assert!((s.apply(64.0) - 40.0).abs() < 1e-9);
+        assert!((s.apply(192.0) - 220.0).abs() < 1e-9);
+        let table = s.table();
+        assert!(table.windows(2).all(|w| w[0] <= w[1]));
+
+        // a plateau stays flat instead of overshooting
+        let plateau = Curve::new(&[(0.0, 0.0), (100.0, 200.0), (150.0, 200.0), (255.0, 255.0)]);
+        assert!(plateau.table()[100..=150].iter().all(|v| *v == 200));
+
+        let image = CanvasImage::from_vec_with_size(vec![64, 64, 192, 7], 1, 1);
+        let curved =
+            image.channel_curves(Channel::Red, &[(0.0, 0.0), (64.0, 40.0), (255.0, 255.0)]);
+        assert_eq!(curved.rgba(0, 0), Some((40, 64, 192, 7)));
+    }
+}
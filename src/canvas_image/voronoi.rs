@@ -0,0 +1,202 @@
+use super::*;
+use crate::color_space::hsv_to_rgb;
+use crate::distance::Distance;
+use rand::rngs::StdRng;
+use rand::seq::index::sample;
+use rand::SeedableRng;
+
+/// Voronoi diagram of `seeds` with the jump flooding algorithm (Rong and Tan): every pixel looks
+/// at the pixels `step` away in all 8 directions and adopts the closest seed any of them knows
+/// about, with the step halving from half the image size down to 1. An extra pass with a step of
+/// 1 at the end fixes nearly all of the few pixels jump flooding gets wrong.
+///
+/// # Returns
+///  for every pixel, row by row, the index of the seed closest to it under `metric`
+pub fn voronoi(width: u32, height: u32, seeds: &[(u32, u32)], metric: Distance) -> Vec<usize> {
+    assert!(!seeds.is_empty(), "need at least one seed");
+    let metric = metric.metric();
+    let (w, h) = (width as i64, height as i64);
+    let index = |x: i64, y: i64| (y * w + x) as usize;
+
+    let mut nearest: Vec<Option<usize>> = vec![None; (width * height) as usize];
+    for (i, (x, y)) in seeds.iter().enumerate() {
+        assert!(*x < width && *y < height, "seeds must be inside the image");
+        nearest[index(*x as i64, *y as i64)] = Some(i);
+    }
+
+    let mut steps = Vec::new();
+    let mut step = (w.max(h) as u64).next_power_of_two() as i64 / 2;
+    while step >= 1 {
+        steps.push(step);
+        step /= 2;
+    }
+    steps.push(1);
+
+    for step in steps {
+        let previous = nearest.clone();
+
+        for (y, x) in iproduct!(0..h, 0..w) {
+            let distance = |seed: usize| {
+                let (sx, sy) = seeds[seed];
+                metric.distance(x as u32, y as u32, sx, sy)
+            };
+
+            let candidates = iproduct!(-1..=1, -1..=1)
+                .map(|(dy, dx)| (x + dx * step, y + dy * step))
+                .filter(|(nx, ny)| (0..w).contains(nx) && (0..h).contains(ny))
+                .filter_map(|(nx, ny)| previous[index(nx, ny)]);
+
+            // ties go to the lower seed index so the result doesn't depend on the visiting order
+            nearest[index(x, y)] =
+                candidates.min_by(|a, b| distance(*a).total_cmp(&distance(*b)).then(a.cmp(b)));
+        }
+    }
+
+    nearest.into_iter().map(Option::unwrap).collect()
+}
+
+impl CanvasImage {
+    /// Stained glass effect, `seed_count` random points split the image into Voronoi cells and
+    /// every cell is painted in the average color of the pixels in it. The same `seed` always
+    /// picks the same points.
+    pub fn voronoi_mosaic(&self, seed_count: u32, metric: Distance, seed: u64) -> CanvasImage {
+        let pixels = (self.width * self.height) as usize;
+        if pixels == 0 {
+            return CanvasImage::from_vec_with_size(Vec::new(), self.width, self.height);
+        }
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        let seeds: Vec<_> = sample(&mut rng, pixels, (seed_count as usize).clamp(1, pixels))
+            .into_iter()
+            .map(|i| (i as u32 % self.width, i as u32 / self.width))
+            .collect();
+
+        self.mosaic(&seeds, metric)
+    }
+
+    fn mosaic(&self, seeds: &[(u32, u32)], metric: Distance) -> CanvasImage {
+        let cells = voronoi(self.width, self.height, seeds, metric);
+
+        let mut sums = vec![([0u64; 4], 0u64); seeds.len()];
+        for (pixel, cell) in self.data.chunks_exact(4).zip(cells.iter()) {
+            let (sum, count) = &mut sums[*cell];
+            sum.iter_mut().zip(pixel).for_each(|(s, v)| *s += *v as u64);
+            *count += 1;
+        }
+        let means: Vec<[u8; 4]> = sums
+            .into_iter()
+            .map(|(sum, count)| sum.map(|s| ((s as f64) / (count as f64)).round() as u8))
+            .collect();
+
+        let rgba = cells.iter().flat_map(|cell| means[*cell]).collect();
+        CanvasImage::from_vec_with_size(rgba, self.width, self.height)
+    }
+}
+
+/// Paint the Voronoi cells of the seeds, given as `[x0, y0, x1, y1, ...]`, in distinct colors
+#[wasm_bindgen]
+pub fn voronoi_diagram(
+    width: u32,
+    height: u32,
+    seeds: &[u32],
+    metric: Distance,
+) -> Result<ImageData, String> {
+    if seeds.is_empty() || !seeds.len().is_multiple_of(2) {
+        return Err(format!(
+            "expected at least one (x, y) pair of seeds, got {} numbers",
+            seeds.len()
+        ));
+    }
+    let seeds: Vec<_> = seeds.chunks_exact(2).map(|xy| (xy[0], xy[1])).collect();
+    if let Some((x, y)) = seeds.iter().find(|(x, y)| *x >= width || *y >= height) {
+        return Err(format!(
+            "seed ({x}, {y}) is outside of the {width} x {height} image"
+        ));
+    }
+
+    // successive golden angle hue steps keep neighbouring indices apart
+    let rgba = voronoi(width, height, &seeds, metric)
+        .into_iter()
+        .flat_map(|cell| {
+            let hue = (cell as f64 * 137.507_764).rem_euclid(360.0);
+            let (r, g, b) = hsv_to_rgb(hue, 0.6, 0.95);
+            [r, g, b]
+                .map(|v| (v * 255.0).round() as u8)
+                .into_iter()
+                .chain([255])
+        })
+        .collect();
+
+    Ok(CanvasImage::from_vec_with_size(rgba, width, height).into())
+}
+
+#[wasm_bindgen]
+pub fn voronoi_mosaic(image: ImageData, seed_count: u32, metric: Distance, seed: u32) -> ImageData {
+    let image = CanvasImage::from_image_data(image);
+    image.voronoi_mosaic(seed_count, metric, seed as u64).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn jump_flooding_finds_the_closest_seed() {
+        let mut rng = StdRng::seed_from_u64(41);
+        let (width, height) = (37, 23);
+        let seeds: Vec<_> = (0..12)
+            .map(|_| (rng.gen_range(0..width), rng.gen_range(0..height)))
+            .collect();
+
+        for metric in [
+            Distance::Manhattan,
+            Distance::Chebyshev,
+            Distance::Euclidean,
+        ] {
+            let cells = voronoi(width, height, &seeds, metric);
+            let distance = |x, y, seed: usize| {
+                let (sx, sy) = seeds[seed];
+                metric.metric().distance(x, y, sx, sy)
+            };
+
+            for (y, x) in iproduct!(0..height, 0..width) {
+                let closest = (0..seeds.len())
+                    .map(|seed| distance(x, y, seed))
+                    .fold(f64::INFINITY, f64::min);
+                let found = distance(x, y, cells[(y * width + x) as usize]);
+                assert_eq!(found, closest, "{metric:?} at ({x}, {y})");
+            }
+        }
+    }
+
+    #[test]
+    fn mosaic_paints_cells_in_their_mean_color() {
+        // left half black, right half grey, with one seed in each half the cells are the halves
+        let rgba = iproduct!(0..4, 0..8).flat_map(|(_, x)| {
+            let v = if x < 4 { 0 } else { 200 };
+            [v, v, v, 255]
+        });
+        let image = CanvasImage::from_vec_with_size(rgba.collect(), 8, 4);
+
+        let mosaic = image.mosaic(&[(1, 1), (6, 1)], Distance::Manhattan);
+        assert_eq!(mosaic.rgba(0, 0), Some((0, 0, 0, 255)));
+        assert_eq!(mosaic.rgba(7, 3), Some((200, 200, 200, 255)));
+
+        let mosaic = image.mosaic(&[(0, 0)], Distance::Euclidean);
+        assert_eq!(mosaic.rgba(3, 2), Some((100, 100, 100, 255)));
+
+        // the same seed gives the same mosaic
+        let random = image.voronoi_mosaic(5, Distance::Chebyshev, 3);
+        assert_eq!(random.horizontal_size(), 8);
+        assert_eq!(
+            random.rgba_slice(),
+            image.voronoi_mosaic(5, Distance::Chebyshev, 3).rgba_slice()
+        );
+
+        let empty = CanvasImage::from_vec_with_size(Vec::new(), 0, 4);
+        let mosaic = empty.voronoi_mosaic(5, Distance::Euclidean, 3);
+        assert!(mosaic.rgba_slice().is_empty());
+    }
+}
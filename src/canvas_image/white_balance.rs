@@ -0,0 +1,196 @@
+use super::*;
+use crate::color_space::{ChromaticAdaptation, LinearRgb, Xyz, D65};
+
+impl CanvasImage {
+    /// Adapt the image from the light whose white shows up in it as `illuminant` to daylight
+    fn adapt_to_daylight(&self, illuminant: Xyz) -> CanvasImage {
+        if illuminant.y <= 0.0 {
+            return CanvasImage::from_vec_with_size(self.data.clone(), self.width, self.height);
+        }
+
+        let white = Xyz {
+            x: illuminant.x / illuminant.y,
+            y: 1.0,
+            z: illuminant.z / illuminant.y,
+        };
+        let adaptation = ChromaticAdaptation::bradford(white, D65);
+        self.map_colors(|color| adaptation.apply(color.into()).into())
+    }
+
+    fn linear_pixels(&self) -> impl Iterator<Item = LinearRgb> + '_ {
+        self.rgba_iter()
+            .map(|(r, g, b, _)| Srgb::from_u8(r, g, b).into())
+    }
+
+    /// Gray world white balance: assume the scene averages out to grey, so the mean color is
+    /// the color of the light
+    pub fn white_balance_gray_world(&self) -> CanvasImage {
+        if self.data.is_empty() {
+            return CanvasImage::from_vec_with_size(Vec::new(), self.width, self.height);
+        }
+
+        let count = (self.width * self.height) as f64;
+        let (r, g, b) = self
+            .linear_pixels()
+            .fold((0.0, 0.0, 0.0), |(r, g, b), c| (r + c.r, g + c.g, b + c.b));
+
+        let mean = LinearRgb {
+            r: r / count,
+            g: g / count,
+            b: b / count,
+        };
+        self.adapt_to_daylight(mean.into())
+    }
+
+    /// White patch white balance: assume the brightest things in the scene are white. Taking
+    /// the `percentile` (in [0, 100]) of every channel instead of its maximum keeps a few
+    /// blown out or noisy pixels from deciding the result.
+    pub fn white_balance_white_patch(&self, percentile: f64) -> CanvasImage {
+        if self.data.is_empty() {
+            return CanvasImage::from_vec_with_size(Vec::new(), self.width, self.height);
+        }
+
+        let mut channels = [Vec::new(), Vec::new(), Vec::new()];
+        for c in self.linear_pixels() {
+            channels[0].push(c.r);
+            channels[1].push(c.g);
+            channels[2].push(c.b);
+        }
+
+        let [r, g, b] = channels.map(|mut values| {
+            values.sort_by(f64::total_cmp);
+            let rank = (percentile.clamp(0.0, 100.0) / 100.0 * (values.len() - 1) as f64).round();
+            values[rank as usize]
+        });
+        self.adapt_to_daylight(LinearRgb { r, g, b }.into())
+    }
+
+    /// White balance from a point the user says is neutral grey or white, the colors in the 3x3
+    /// neighbourhood of (`x`, `y`) are averaged to cut down noise
+    pub fn white_balance_from_point(&self, x: u32, y: u32) -> CanvasImage {
+        assert!(
+            x < self.width && y < self.height,
+            "the point must be inside the image"
+        );
+
+        let neighbourhood: Vec<LinearRgb> = iproduct!(-1..=1, -1..=1)
+            .map(|(dy, dx)| (x as i64 + dx, y as i64 + dy))
+            .filter(|(x, y)| {
+                (0..self.width as i64).contains(x) && (0..self.height as i64).contains(y)
+            })
+            .map(|(x, y)| {
+                let (r, g, b, _) = self.rgba(x as u32, y as u32).unwrap();
+                Srgb::from_u8(r, g, b).into()
+            })
+            .collect();
+
+        let count = neighbourhood.len() as f64;
+        let sum = |f: fn(&LinearRgb) -> f64| neighbourhood.iter().map(f).sum::<f64>() / count;
+        let mean = LinearRgb {
+            r: sum(|c| c.r),
+            g: sum(|c| c.g),
+            b: sum(|c| c.b),
+        };
+        self.adapt_to_daylight(mean.into())
+    }
+
+    /// Correct for a light of `kelvin` with `tint`, see `Xyz::white_point`. Telling it the
+    /// photo was taken under warm 3000K light makes it cooler and vice versa.
+    pub fn set_temperature_tint(&self, kelvin: f64, tint: f64) -> CanvasImage {
+        self.adapt_to_daylight(Xyz::white_point(kelvin, tint))
+    }
+}
+
+#[wasm_bindgen]
+pub fn white_balance_gray_world(image: ImageData) -> ImageData {
+    let image = CanvasImage::from_image_data(image);
+    image.white_balance_gray_world().into()
+}
+
+#[wasm_bindgen]
+pub fn white_balance_white_patch(image: ImageData, percentile: f64) -> ImageData {
+    let image = CanvasImage::from_image_data(image);
+    image.white_balance_white_patch(percentile).into()
+}
+
+#[wasm_bindgen]
+pub fn white_balance_from_point(image: ImageData, x: u32, y: u32) -> Result<ImageData, String> {
+    let image = CanvasImage::from_image_data(image);
+    check_point(&image, x, y)?;
+    Ok(image.white_balance_from_point(x, y).into())
+}
+
+#[wasm_bindgen]
+pub fn set_temperature_tint(image: ImageData, kelvin: f64, tint: f64) -> ImageData {
+    let image = CanvasImage::from_image_data(image);
+    image.set_temperature_tint(kelvin, tint).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_neutral(pixel: Option<(u8, u8, u8, u8)>) -> bool {
+        let (r, g, b, _) = pixel.unwrap();
+        r.abs_diff(g) <= 1 && g.abs_diff(b) <= 1
+    }
+
+    /// Greys and white seen under warm light
+    fn warm_scene() -> CanvasImage {
+        let rgba = [0.1, 0.3, 0.6, 0.9].iter().flat_map(|k| {
+            let light = LinearRgb {
+                r: k * 1.0,
+                g: k * 0.8,
+                b: k * 0.55,
+            };
+            let (r, g, b) = Srgb::from(light).to_u8();
+            [r, g, b, 128]
+        });
+        CanvasImage::from_vec_with_size(rgba.collect(), 4, 1)
+    }
+
+    #[test]
+    fn casts_are_removed() {
+        let image = warm_scene();
+
+        let white_patch = image.white_balance_white_patch(100.0);
+        assert!(is_neutral(white_patch.rgba(3, 0)));
+        assert_eq!(white_patch.a(3, 0), Some(128));
+
+        let picked = image.white_balance_from_point(0, 0);
+        assert!((0..4).all(|x| is_neutral(picked.rgba(x, 0))));
+
+        let gray_world = image.white_balance_gray_world();
+        assert!((0..4).all(|x| is_neutral(gray_world.rgba(x, 0))));
+    }
+
+    #[test]
+    fn temperature() {
+        let grey = CanvasImage::from_vec_with_size(vec![128, 128, 128, 255], 1, 1);
+
+        // daylight changes nothing
+        assert!(is_neutral(
+            grey.set_temperature_tint(6504.0, 0.0).rgba(0, 0)
+        ));
+
+        // correcting for warm light cools the image down, for cool light warms it up
+        let (r, _, b, _) = grey.set_temperature_tint(3000.0, 0.0).rgba(0, 0).unwrap();
+        assert!(b > r);
+        let (r, _, b, _) = grey.set_temperature_tint(10000.0, 0.0).rgba(0, 0).unwrap();
+        assert!(r > b);
+
+        // correcting for a magenta tint makes it greener
+        let (r, g, _, _) = grey.set_temperature_tint(6504.0, 10.0).rgba(0, 0).unwrap();
+        assert!(g > r);
+    }
+
+    #[test]
+    fn empty_images() {
+        let empty = CanvasImage::from_vec_with_size(Vec::new(), 0, 0);
+        assert!(empty.white_balance_gray_world().rgba_slice().is_empty());
+        assert!(empty
+            .white_balance_white_patch(99.0)
+            .rgba_slice()
+            .is_empty());
+    }
+}
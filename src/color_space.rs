@@ -1,4 +1,5 @@
 use crate::canvas_image::CanvasImage;
+use nalgebra::{Matrix3, Vector3};
 use wasm_bindgen::prelude::*;
 use web_sys::ImageData;
 
@@ -33,9 +34,789 @@ pub fn to_srgb(linear: f64) -> f64 {
     }
 }
 
+/// Convert HSV to RGB, hue in degrees [0, 360), saturation and value in [0, 1]
+pub fn hsv_to_rgb(hue: f64, saturation: f64, value: f64) -> (f64, f64, f64) {
+    let Srgb { r, g, b } = Hsv {
+        h: hue,
+        s: saturation,
+        v: value,
+    }
+    .into();
+    (r, g, b)
+}
+
+/// Convert sRGB in [0, 1] to CIELAB under the D65 white point, L in [0, 100]
+pub fn srgb_to_lab(r: f64, g: f64, b: f64) -> (f64, f64, f64) {
+    let Lab { l, a, b } = Srgb { r, g, b }.into();
+    (l, a, b)
+}
+
+/************************************ typed colors *************************************/
+
+/// Gamma encoded sRGB, every component in [0, 1]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Srgb {
+    pub r: f64,
+    pub g: f64,
+    pub b: f64,
+}
+
+/// sRGB primaries without the transfer function, proportional to light intensity
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LinearRgb {
+    pub r: f64,
+    pub g: f64,
+    pub b: f64,
+}
+
+/// Hue in degrees [0, 360), saturation and value in [0, 1]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Hsv {
+    pub h: f64,
+    pub s: f64,
+    pub v: f64,
+}
+
+/// Hue in degrees [0, 360), saturation and lightness in [0, 1]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Hsl {
+    pub h: f64,
+    pub s: f64,
+    pub l: f64,
+}
+
+/// CIE 1931 XYZ with the D65 white point, white has Y = 1
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Xyz {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+/// CIE L*a*b* relative to D65, L in [0, 100], a and b roughly in [-128, 127]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Lab {
+    pub l: f64,
+    pub a: f64,
+    pub b: f64,
+}
+
+/// L*a*b* in polar coordinates: lightness, chroma and hue in degrees [0, 360)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Lch {
+    pub l: f64,
+    pub c: f64,
+    pub h: f64,
+}
+
+/// Luma in [0, 1] and the two chroma differences in [-0.5, 0.5], from gamma encoded RGB
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct YCbCr {
+    pub y: f64,
+    pub cb: f64,
+    pub cr: f64,
+}
+
+/// Björn Ottosson's perceptual color space, L in [0, 1], a and b roughly in [-0.4, 0.4]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Oklab {
+    pub l: f64,
+    pub a: f64,
+    pub b: f64,
+}
+
+/// The luma weights of a YCbCr encoding
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum YCbCrStandard {
+    /// Standard definition video and JPEG
+    Bt601,
+    /// HD video
+    Bt709,
+}
+
+impl YCbCrStandard {
+    /// (Kr, Kb), the weights of red and blue in the luma
+    fn weights(&self) -> (f64, f64) {
+        match self {
+            YCbCrStandard::Bt601 => (0.299, 0.114),
+            YCbCrStandard::Bt709 => (0.2126, 0.0722),
+        }
+    }
+}
+
+/// D65 white point, normalized to Y = 1
+pub const D65: Xyz = Xyz {
+    x: 0.95047,
+    y: 1.0,
+    z: 1.08883,
+};
+
+/// Put an angle in degrees into [0, 360)
+fn wrap_degrees(degrees: f64) -> f64 {
+    let wrapped = degrees.rem_euclid(360.0);
+    // rem_euclid can round up to exactly 360 for tiny negative angles
+    if wrapped >= 360.0 {
+        0.0
+    } else {
+        wrapped
+    }
+}
+
+impl Srgb {
+    pub fn new(r: f64, g: f64, b: f64) -> Srgb {
+        Srgb { r, g, b }
+    }
+
+    /// From 8 bit components
+    pub fn from_u8(r: u8, g: u8, b: u8) -> Srgb {
+        Srgb::new(r as f64 / 255.0, g as f64 / 255.0, b as f64 / 255.0)
+    }
+
+    /// To 8 bit components, out of gamut colors are clamped
+    pub fn to_u8(self) -> (u8, u8, u8) {
+        let [r, g, b] =
+            [self.r, self.g, self.b].map(|v| (v * 255.0).round().clamp(0.0, 255.0) as u8);
+        (r, g, b)
+    }
+
+    /// Hue in degrees, chroma, and the largest and smallest component, shared by HSV and HSL
+    fn hue_chroma(&self) -> (f64, f64, f64, f64) {
+        let max = self.r.max(self.g).max(self.b);
+        let min = self.r.min(self.g).min(self.b);
+        let chroma = max - min;
+
+        let hue = if chroma == 0.0 {
+            0.0
+        } else if max == self.r {
+            60.0 * ((self.g - self.b) / chroma)
+        } else if max == self.g {
+            60.0 * ((self.b - self.r) / chroma + 2.0)
+        } else {
+            60.0 * ((self.r - self.g) / chroma + 4.0)
+        };
+
+        (wrap_degrees(hue), chroma, max, min)
+    }
+
+    /// RGB with the given hue and chroma whose smallest component is `m`
+    fn from_hue_chroma(hue: f64, chroma: f64, m: f64) -> Srgb {
+        let sector = wrap_degrees(hue) / 60.0;
+        let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+
+        let (r, g, b) = match sector as u32 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+
+        Srgb::new(r + m, g + m, b + m)
+    }
+}
+
+impl From<Srgb> for LinearRgb {
+    fn from(c: Srgb) -> Self {
+        LinearRgb {
+            r: c.r.linearize(),
+            g: c.g.linearize(),
+            b: c.b.linearize(),
+        }
+    }
+}
+
+impl From<LinearRgb> for Srgb {
+    fn from(c: LinearRgb) -> Self {
+        Srgb::new(to_srgb(c.r), to_srgb(c.g), to_srgb(c.b))
+    }
+}
+
+impl From<Srgb> for Hsv {
+    fn from(c: Srgb) -> Self {
+        let (h, chroma, max, _) = c.hue_chroma();
+        let s = if max == 0.0 { 0.0 } else { chroma / max };
+
+        Hsv { h, s, v: max }
+    }
+}
+
+// Plagiarized from wikipedia
+impl From<Hsv> for Srgb {
+    fn from(c: Hsv) -> Self {
+        let chroma = c.v * c.s;
+        Srgb::from_hue_chroma(c.h, chroma, c.v - chroma)
+    }
+}
+
+impl From<Srgb> for Hsl {
+    fn from(c: Srgb) -> Self {
+        let (h, chroma, max, min) = c.hue_chroma();
+        let l = (max + min) / 2.0;
+        let s = if l == 0.0 || l == 1.0 {
+            0.0
+        } else {
+            chroma / (1.0 - (2.0 * l - 1.0).abs())
+        };
+
+        Hsl { h, s, l }
+    }
+}
+
+// Plagiarized from wikipedia
+impl From<Hsl> for Srgb {
+    fn from(c: Hsl) -> Self {
+        let chroma = (1.0 - (2.0 * c.l - 1.0).abs()) * c.s;
+        Srgb::from_hue_chroma(c.h, chroma, c.l - chroma / 2.0)
+    }
+}
+
+impl From<LinearRgb> for Xyz {
+    fn from(c: LinearRgb) -> Self {
+        Xyz {
+            x: 0.4124564 * c.r + 0.3575761 * c.g + 0.1804375 * c.b,
+            y: 0.2126729 * c.r + 0.7151522 * c.g + 0.0721750 * c.b,
+            z: 0.0193339 * c.r + 0.1191920 * c.g + 0.9503041 * c.b,
+        }
+    }
+}
+
+impl From<Xyz> for LinearRgb {
+    fn from(c: Xyz) -> Self {
+        LinearRgb {
+            r: 3.2404542 * c.x - 1.5371385 * c.y - 0.4985314 * c.z,
+            g: -0.9692660 * c.x + 1.8760108 * c.y + 0.0415560 * c.z,
+            b: 0.0556434 * c.x - 0.2040259 * c.y + 1.0572252 * c.z,
+        }
+    }
+}
+
+/// Lab's companding, cube root with a linear segment near 0
+const LAB_DELTA: f64 = 6.0 / 29.0;
+
+impl From<Xyz> for Lab {
+    fn from(c: Xyz) -> Self {
+        let f = |t: f64| {
+            if t > LAB_DELTA.powi(3) {
+                t.cbrt()
+            } else {
+                t / (3.0 * LAB_DELTA * LAB_DELTA) + 4.0 / 29.0
+            }
+        };
+        let (fx, fy, fz) = (f(c.x / D65.x), f(c.y / D65.y), f(c.z / D65.z));
+
+        Lab {
+            l: 116.0 * fy - 16.0,
+            a: 500.0 * (fx - fy),
+            b: 200.0 * (fy - fz),
+        }
+    }
+}
+
+impl From<Lab> for Xyz {
+    fn from(c: Lab) -> Self {
+        let f_inverse = |t: f64| {
+            if t > LAB_DELTA {
+                t.powi(3)
+            } else {
+                3.0 * LAB_DELTA * LAB_DELTA * (t - 4.0 / 29.0)
+            }
+        };
+        let fy = (c.l + 16.0) / 116.0;
+
+        Xyz {
+            x: D65.x * f_inverse(fy + c.a / 500.0),
+            y: D65.y * f_inverse(fy),
+            z: D65.z * f_inverse(fy - c.b / 200.0),
+        }
+    }
+}
+
+impl From<Lab> for Lch {
+    fn from(c: Lab) -> Self {
+        Lch {
+            l: c.l,
+            c: f64::hypot(c.a, c.b),
+            h: wrap_degrees(c.b.atan2(c.a).to_degrees()),
+        }
+    }
+}
+
+impl From<Lch> for Lab {
+    fn from(c: Lch) -> Self {
+        let (sin, cos) = c.h.to_radians().sin_cos();
+        Lab {
+            l: c.l,
+            a: c.c * cos,
+            b: c.c * sin,
+        }
+    }
+}
+
+// https://bottosson.github.io/posts/oklab/
+impl From<LinearRgb> for Oklab {
+    fn from(c: LinearRgb) -> Self {
+        let l = (0.4122214708 * c.r + 0.5363325363 * c.g + 0.0514459929 * c.b).cbrt();
+        let m = (0.2119034982 * c.r + 0.6806995451 * c.g + 0.1073969566 * c.b).cbrt();
+        let s = (0.0883024619 * c.r + 0.2817188376 * c.g + 0.6299787005 * c.b).cbrt();
+
+        Oklab {
+            l: 0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+            a: 1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+            b: 0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+        }
+    }
+}
+
+impl From<Oklab> for LinearRgb {
+    fn from(c: Oklab) -> Self {
+        let l = (c.l + 0.3963377774 * c.a + 0.2158037573 * c.b).powi(3);
+        let m = (c.l - 0.1055613458 * c.a - 0.0638541728 * c.b).powi(3);
+        let s = (c.l - 0.0894841775 * c.a - 1.2914855480 * c.b).powi(3);
+
+        LinearRgb {
+            r: 4.0767416621 * l - 3.3077115913 * m + 0.2309699292 * s,
+            g: -1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s,
+            b: -0.0041960863 * l - 0.7034186147 * m + 1.7076147010 * s,
+        }
+    }
+}
+
+impl YCbCr {
+    pub fn from_srgb(c: Srgb, standard: YCbCrStandard) -> YCbCr {
+        let (kr, kb) = standard.weights();
+        let y = kr * c.r + (1.0 - kr - kb) * c.g + kb * c.b;
+
+        YCbCr {
+            y,
+            cb: (c.b - y) / (2.0 * (1.0 - kb)),
+            cr: (c.r - y) / (2.0 * (1.0 - kr)),
+        }
+    }
+
+    pub fn to_srgb(self, standard: YCbCrStandard) -> Srgb {
+        let (kr, kb) = standard.weights();
+        let r = self.y + 2.0 * (1.0 - kr) * self.cr;
+        let b = self.y + 2.0 * (1.0 - kb) * self.cb;
+        let g = (self.y - kr * r - kb * b) / (1.0 - kr - kb);
+
+        Srgb::new(r, g, b)
+    }
+}
+
+/// Conversions that go through intermediate spaces
+macro_rules! convert_through {
+    ($from:ty => $via:ty => $to:ty) => {
+        impl From<$from> for $to {
+            fn from(c: $from) -> Self {
+                <$to>::from(<$via>::from(c))
+            }
+        }
+    };
+}
+
+convert_through!(Srgb => LinearRgb => Xyz);
+convert_through!(Xyz => LinearRgb => Srgb);
+convert_through!(Srgb => Xyz => Lab);
+convert_through!(Lab => Xyz => Srgb);
+convert_through!(Srgb => Lab => Lch);
+convert_through!(Lch => Lab => Srgb);
+convert_through!(Srgb => LinearRgb => Oklab);
+convert_through!(Oklab => LinearRgb => Srgb);
+
+impl Xyz {
+    /// The color with chromaticity (`x`, `y`) and Y = 1
+    pub fn from_chromaticity(x: f64, y: f64) -> Xyz {
+        Xyz {
+            x: x / y,
+            y: 1.0,
+            z: (1.0 - x - y) / y,
+        }
+    }
+
+    /// White of a light source at `kelvin`: on the Planckian locus below 4000K (the
+    /// approximation of Kim et al.) and on the CIE daylight locus above, so 6504K is D65. Valid
+    /// from 1667K to 25000K. `tint` moves it off the locus in units of 0.001 in the CIE 1960 uv
+    /// plane, positive values towards magenta and negative ones towards green.
+    pub fn white_point(kelvin: f64, tint: f64) -> Xyz {
+        let locus = |t: f64| {
+            let t = t.clamp(1667.0, 25000.0);
+            let (x, y) = if t < 4000.0 {
+                let x =
+                    -0.2661239e9 / t.powi(3) - 0.2343589e6 / t.powi(2) + 0.8776956e3 / t + 0.179910;
+                let y = if t <= 2222.0 {
+                    -1.1063814 * x.powi(3) - 1.34811020 * x.powi(2) + 2.18555832 * x - 0.20219683
+                } else {
+                    -0.9549476 * x.powi(3) - 1.37418593 * x.powi(2) + 2.09137015 * x - 0.16748867
+                };
+                (x, y)
+            } else {
+                let x = if t <= 7000.0 {
+                    -4.6070e9 / t.powi(3) + 2.9678e6 / t.powi(2) + 0.09911e3 / t + 0.244063
+                } else {
+                    -2.0064e9 / t.powi(3) + 1.9018e6 / t.powi(2) + 0.24748e3 / t + 0.237040
+                };
+                (x, -3.0 * x * x + 2.87 * x - 0.275)
+            };
+
+            let d = -2.0 * x + 12.0 * y + 3.0;
+            (4.0 * x / d, 6.0 * y / d)
+        };
+
+        // the normal of the locus pointing away from green, found from a nearby point on it
+        let (u, v) = locus(kelvin);
+        let (nu, nv) = locus(kelvin.clamp(1667.0, 24990.0) + 10.0);
+        let (du, dv) = (nu - u, nv - v);
+        let length = du.hypot(dv);
+        let (normal_u, normal_v) = if du < 0.0 {
+            (dv / length, -du / length)
+        } else {
+            (-dv / length, du / length)
+        };
+        let (u, v) = (u - normal_u * tint / 1000.0, v - normal_v * tint / 1000.0);
+
+        let d = 2.0 * u - 8.0 * v + 4.0;
+        Xyz::from_chromaticity(3.0 * u / d, 2.0 * v / d)
+    }
+}
+
+/// Bradford cone response matrix
+#[rustfmt::skip]
+const BRADFORD: [f64; 9] = [
+     0.8951,  0.2664, -0.1614,
+    -0.7502,  1.7135,  0.0367,
+     0.0389, -0.0685,  1.0296,
+];
+
+/// Bradford chromatic adaptation: turns colors seen under one white into how they look under
+/// another, by scaling the cone responses
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChromaticAdaptation {
+    matrix: Matrix3<f64>,
+}
+
+impl ChromaticAdaptation {
+    pub fn bradford(source_white: Xyz, target_white: Xyz) -> ChromaticAdaptation {
+        let cone = Matrix3::from_row_slice(&BRADFORD);
+        let response = |w: Xyz| cone * Vector3::new(w.x, w.y, w.z);
+        let (source, target) = (response(source_white), response(target_white));
+        let scale = Matrix3::from_diagonal(&target.component_div(&source));
+
+        ChromaticAdaptation {
+            matrix: cone.try_inverse().unwrap() * scale * cone,
+        }
+    }
+
+    pub fn apply(&self, c: Xyz) -> Xyz {
+        let adapted = self.matrix * Vector3::new(c.x, c.y, c.z);
+        Xyz {
+            x: adapted.x,
+            y: adapted.y,
+            z: adapted.z,
+        }
+    }
+}
+
+/// A color space an image can be split into three planes of
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ColorSpace {
+    Srgb,
+    LinearRgb,
+    Hsv,
+    Hsl,
+    Xyz,
+    Lab,
+    Lch,
+    YCbCr601,
+    YCbCr709,
+    Oklab,
+}
+
+impl ColorSpace {
+    /// The three components of `color` in this space, in the order and units of its struct
+    pub fn from_srgb(&self, color: Srgb) -> [f64; 3] {
+        match self {
+            ColorSpace::Srgb => [color.r, color.g, color.b],
+            ColorSpace::LinearRgb => {
+                let c = LinearRgb::from(color);
+                [c.r, c.g, c.b]
+            }
+            ColorSpace::Hsv => {
+                let c = Hsv::from(color);
+                [c.h, c.s, c.v]
+            }
+            ColorSpace::Hsl => {
+                let c = Hsl::from(color);
+                [c.h, c.s, c.l]
+            }
+            ColorSpace::Xyz => {
+                let c = Xyz::from(color);
+                [c.x, c.y, c.z]
+            }
+            ColorSpace::Lab => {
+                let c = Lab::from(color);
+                [c.l, c.a, c.b]
+            }
+            ColorSpace::Lch => {
+                let c = Lch::from(color);
+                [c.l, c.c, c.h]
+            }
+            ColorSpace::YCbCr601 | ColorSpace::YCbCr709 => {
+                let c = YCbCr::from_srgb(color, self.ycbcr_standard());
+                [c.y, c.cb, c.cr]
+            }
+            ColorSpace::Oklab => {
+                let c = Oklab::from(color);
+                [c.l, c.a, c.b]
+            }
+        }
+    }
+
+    /// Back from the three components of this space
+    pub fn to_srgb(&self, [p, q, r]: [f64; 3]) -> Srgb {
+        match self {
+            ColorSpace::Srgb => Srgb::new(p, q, r),
+            ColorSpace::LinearRgb => LinearRgb { r: p, g: q, b: r }.into(),
+            ColorSpace::Hsv => Hsv { h: p, s: q, v: r }.into(),
+            ColorSpace::Hsl => Hsl { h: p, s: q, l: r }.into(),
+            ColorSpace::Xyz => Xyz { x: p, y: q, z: r }.into(),
+            ColorSpace::Lab => Lab { l: p, a: q, b: r }.into(),
+            ColorSpace::Lch => Lch { l: p, c: q, h: r }.into(),
+            ColorSpace::YCbCr601 | ColorSpace::YCbCr709 => {
+                YCbCr { y: p, cb: q, cr: r }.to_srgb(self.ycbcr_standard())
+            }
+            ColorSpace::Oklab => Oklab { l: p, a: q, b: r }.into(),
+        }
+    }
+
+    fn ycbcr_standard(&self) -> YCbCrStandard {
+        match self {
+            ColorSpace::YCbCr709 => YCbCrStandard::Bt709,
+            _ => YCbCrStandard::Bt601,
+        }
+    }
+}
+
+/// How a color is turned into a single grey value
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GreyscaleMethod {
+    /// Relative luminance, computed in linear light and gamma encoded again
+    Luminance,
+    /// BT.601 luma, weighted sum of the gamma encoded components
+    Rec601,
+    /// BT.709 luma, weighted sum of the gamma encoded components
+    Rec709,
+    /// Mean of the three components
+    Average,
+    /// HSL lightness, the mean of the largest and the smallest component
+    Lightness,
+    /// Only the red component
+    Red,
+    /// Only the green component
+    Green,
+    /// Only the blue component
+    Blue,
+    /// Drop the chroma in CIELAB and convert back
+    LabDesaturate,
+}
+
+impl GreyscaleMethod {
+    /// The grey value of `color`, in [0, 1]
+    pub fn grey(&self, color: Srgb) -> f64 {
+        let Srgb { r, g, b } = color;
+        let luma = |(kr, kb): (f64, f64)| kr * r + (1.0 - kr - kb) * g + kb * b;
+
+        match self {
+            GreyscaleMethod::Luminance => {
+                let LinearRgb { r, g, b } = color.into();
+                to_srgb(to_luminance(r, g, b))
+            }
+            GreyscaleMethod::Rec601 => luma(YCbCrStandard::Bt601.weights()),
+            GreyscaleMethod::Rec709 => luma(YCbCrStandard::Bt709.weights()),
+            GreyscaleMethod::Average => (r + g + b) / 3.0,
+            GreyscaleMethod::Lightness => Hsl::from(color).l,
+            GreyscaleMethod::Red => r,
+            GreyscaleMethod::Green => g,
+            GreyscaleMethod::Blue => b,
+            GreyscaleMethod::LabDesaturate => {
+                let Lab { l, .. } = color.into();
+                Srgb::from(Lab { l, a: 0.0, b: 0.0 }).g
+            }
+        }
+        .clamp(0.0, 1.0)
+    }
+}
+
 #[wasm_bindgen]
 pub fn faster_greyscale(image: ImageData) -> Vec<u8> {
     let mut image = CanvasImage::from_image_data(image);
     image.convert_to_greyscale();
     image.into()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use itertools::iproduct;
+
+    fn assert_close(a: Srgb, b: Srgb) {
+        for (x, y) in [(a.r, b.r), (a.g, b.g), (a.b, b.b)] {
+            assert!((x - y).abs() < 1e-5, "{a:?} != {b:?}");
+        }
+    }
+
+    #[test]
+    fn every_space_round_trips() {
+        let steps = [0.0, 0.1, 0.25, 0.5, 0.8, 1.0];
+        let spaces = [
+            ColorSpace::Srgb,
+            ColorSpace::LinearRgb,
+            ColorSpace::Hsv,
+            ColorSpace::Hsl,
+            ColorSpace::Xyz,
+            ColorSpace::Lab,
+            ColorSpace::Lch,
+            ColorSpace::YCbCr601,
+            ColorSpace::YCbCr709,
+            ColorSpace::Oklab,
+        ];
+
+        for (space, r, g, b) in iproduct!(spaces, steps, steps, steps) {
+            let color = Srgb::new(r, g, b);
+            assert_close(space.to_srgb(space.from_srgb(color)), color);
+        }
+    }
+
+    #[test]
+    fn planes_round_trip() {
+        let rgba =
+            (0..64u32).flat_map(|i| [(i * 4) as u8, (255 - i * 3) as u8, (i * i % 256) as u8, 100]);
+        let image = CanvasImage::from_vec_with_size(rgba.collect(), 8, 8);
+
+        for space in [
+            ColorSpace::Lab,
+            ColorSpace::Hsl,
+            ColorSpace::YCbCr709,
+            ColorSpace::Oklab,
+        ] {
+            let planes = image.to_planes(space);
+
+            let mut copy = CanvasImage::from_vec_with_size(vec![0; 256], 8, 8);
+            copy.set_planes(&planes, space);
+            let rgb = |image: &CanvasImage| -> Vec<_> {
+                image.rgba_iter().map(|(r, g, b, _)| (r, g, b)).collect()
+            };
+            assert_eq!(rgb(&copy), rgb(&image));
+            assert_eq!(CanvasImage::from_planes(&planes, space).a(3, 3), Some(255));
+        }
+    }
+
+    #[test]
+    fn greyscale_methods() {
+        let methods = [
+            GreyscaleMethod::Luminance,
+            GreyscaleMethod::Rec601,
+            GreyscaleMethod::Rec709,
+            GreyscaleMethod::Average,
+            GreyscaleMethod::Lightness,
+            GreyscaleMethod::Red,
+            GreyscaleMethod::Green,
+            GreyscaleMethod::Blue,
+            GreyscaleMethod::LabDesaturate,
+        ];
+
+        // greys stay where they are
+        for method in methods {
+            let grey = method.grey(Srgb::new(0.4, 0.4, 0.4));
+            assert!((grey - 0.4).abs() < 1e-6, "{method:?}");
+        }
+
+        let orange = Srgb::new(1.0, 0.5, 0.0);
+        assert!((GreyscaleMethod::Rec601.grey(orange) - 0.5925).abs() < 1e-12);
+        assert!((GreyscaleMethod::Average.grey(orange) - 0.5).abs() < 1e-12);
+        assert_eq!(GreyscaleMethod::Lightness.grey(orange), 0.5);
+        assert_eq!(GreyscaleMethod::Blue.grey(orange), 0.0);
+
+        // desaturating in Lab keeps the luminance
+        let luminance = GreyscaleMethod::Luminance.grey(orange);
+        assert!((GreyscaleMethod::LabDesaturate.grey(orange) - luminance).abs() < 1e-4);
+    }
+
+    #[test]
+    fn greyscale_conversion_keeps_greys() {
+        let rgba = vec![
+            0, 0, 0, 255, 128, 128, 128, 255, 255, 255, 255, 10, 255, 0, 0, 255,
+        ];
+        let mut image = CanvasImage::from_vec_with_size(rgba, 4, 1);
+        image.convert_to_greyscale();
+
+        assert_eq!(image.rgba(0, 0), Some((0, 0, 0, 255)));
+        assert_eq!(image.rgba(1, 0), Some((128, 128, 128, 255)));
+        assert_eq!(image.rgba(2, 0), Some((255, 255, 255, 10)));
+        // red has a luminance of 0.2126, which is 127 once gamma encoded
+        assert_eq!(image.rgba(3, 0), Some((127, 127, 127, 255)));
+        assert_eq!(
+            image.greyscale_plane(GreyscaleMethod::Red).get(3, 0),
+            Some(127.0 / 255.0)
+        );
+    }
+
+    #[test]
+    fn white_points_and_adaptation() {
+        // D65 is daylight at about 6504K
+        let daylight = Xyz::white_point(6504.0, 0.0);
+        assert!((daylight.x - D65.x).abs() < 1e-3 && (daylight.z - D65.z).abs() < 1e-3);
+
+        // lower temperatures are warmer, the tint moves along the green-magenta axis
+        let tungsten = Srgb::from(Xyz::white_point(2856.0, 0.0));
+        assert!(tungsten.r > tungsten.g && tungsten.g > tungsten.b);
+        let magenta = Srgb::from(Xyz::white_point(6504.0, 10.0));
+        let green = Srgb::from(Xyz::white_point(6504.0, -10.0));
+        assert!(magenta.g < magenta.r && green.g > green.r);
+
+        let adaptation = ChromaticAdaptation::bradford(Xyz::white_point(2856.0, 0.0), D65);
+        let adapted = adaptation.apply(Xyz::white_point(2856.0, 0.0));
+        assert!((adapted.x - D65.x).abs() < 1e-9);
+        assert!((adapted.y - D65.y).abs() < 1e-9);
+        assert!((adapted.z - D65.z).abs() < 1e-9);
+    }
+
+    #[test]
+    fn known_values() {
+        let white = Srgb::new(1.0, 1.0, 1.0);
+        let red = Srgb::new(1.0, 0.0, 0.0);
+
+        let lab = Lab::from(white);
+        assert!((lab.l - 100.0).abs() < 1e-3 && lab.a.abs() < 1e-2 && lab.b.abs() < 1e-2);
+        assert!((Oklab::from(white).l - 1.0).abs() < 1e-6);
+        assert!((Xyz::from(white).y - 1.0).abs() < 1e-6);
+
+        assert_eq!(
+            Hsv::from(red),
+            Hsv {
+                h: 0.0,
+                s: 1.0,
+                v: 1.0
+            }
+        );
+        assert_eq!(
+            Hsl::from(red),
+            Hsl {
+                h: 0.0,
+                s: 1.0,
+                l: 0.5
+            }
+        );
+        assert_eq!(Hsv::from(Srgb::new(0.0, 0.0, 1.0)).h, 240.0);
+
+        let ycbcr = YCbCr::from_srgb(red, YCbCrStandard::Bt601);
+        assert!((ycbcr.y - 0.299).abs() < 1e-12 && (ycbcr.cr - 0.5).abs() < 1e-12);
+
+        // red's Lab hue is a bit above 40 degrees
+        let lch = Lch::from(red);
+        assert!((lch.l - 53.24).abs() < 0.01 && (39.0..41.0).contains(&lch.h));
+    }
+}
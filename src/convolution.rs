@@ -1,4 +1,5 @@
 use crate::canvas_image::CanvasImage;
+use crate::float_image::FloatImage;
 use crate::image_index::{CircularIndexedImage, ReflectiveIndexedImage, ZeroPaddedImage};
 use crate::utils;
 use itertools::iproduct;
@@ -195,6 +196,126 @@ impl Kernel {
             height,
         }
     }
+
+    /// A normalized 2D Gaussian kernel with standard deviation `sigma`, wide enough to cover
+    /// 3 sigma on each side of the center
+    pub fn gaussian(sigma: f64) -> Kernel {
+        let weights = gaussian_weights(sigma);
+        let size = weights.len();
+
+        let data = iproduct!(weights.iter(), weights.iter())
+            .map(|(a, b)| a * b)
+            .collect();
+
+        Kernel::from_vec(data, size, size)
+    }
+
+    /// A Laplacian of Gaussian kernel with standard deviation `sigma`, wide enough to cover
+    /// 3 sigma on each side of the center. It is scale normalized (multiplied by sigma^2) so the
+    /// response to an edge doesn't depend on sigma, and shifted so the weights sum up to 0.
+    pub fn laplacian_of_gaussian(sigma: f64) -> Kernel {
+        assert!(sigma > 0.0, "sigma must be positive");
+
+        let radius = (3.0 * sigma).ceil() as isize;
+        let size = (2 * radius + 1) as usize;
+        let s2 = sigma * sigma;
+
+        let weights: Vec<_> = iproduct!(-radius..=radius, -radius..=radius)
+            .map(|(y, x)| {
+                let r2 = (x * x + y * y) as f64;
+                (r2 / (2.0 * s2) - 1.0) / (std::f64::consts::PI * s2) * (-r2 / (2.0 * s2)).exp()
+            })
+            .collect();
+
+        let mean = weights.iter().sum::<f64>() / weights.len() as f64;
+        let data = weights.into_iter().map(|w| w - mean).collect();
+
+        Kernel::from_vec(data, size, size)
+    }
+}
+
+/// Sampled and normalized 1D Gaussian with standard deviation `sigma`, the length is always odd
+/// so the center sits at `len / 2`
+pub fn gaussian_weights(sigma: f64) -> Vec<f64> {
+    assert!(sigma > 0.0, "sigma must be positive");
+
+    let radius = (3.0 * sigma).ceil() as isize;
+    let weights: Vec<_> = (-radius..=radius)
+        .map(|i| (-((i * i) as f64) / (2.0 * sigma * sigma)).exp())
+        .collect();
+
+    let sum: f64 = weights.iter().sum();
+    weights.into_iter().map(|w| w / sum).collect()
+}
+
+impl FloatImage {
+    /// Convolve the image with a kernel, points outside of the image are reflected back in
+    pub fn convolve(&self, kernel: &Kernel) -> FloatImage {
+        let half_width = (kernel.width / 2) as isize;
+        let half_height = (kernel.height / 2) as isize;
+
+        FloatImage::from_fn(self.horizontal_size(), self.vertical_size(), |x, y| {
+            let mut acc = 0f64;
+
+            for i in -half_height..=half_height {
+                for j in -half_width..=half_width {
+                    let intensity = self.reflective(x as i64 - i as i64, y as i64 - j as i64);
+                    acc += kernel[(i, j)] * intensity;
+                }
+            }
+
+            acc
+        })
+    }
+
+    /// Slide the kernel over the image without flipping it, with the kernel data read row by row
+    /// the way it is written out, i.e. the first row of the kernel lines up with the row above the
+    /// pixel. That is what directional kernels like Sobel expect. Points outside of the image are
+    /// reflected back in.
+    pub fn correlate(&self, kernel: &Kernel) -> FloatImage {
+        let half_width = (kernel.width / 2) as i64;
+        let half_height = (kernel.height / 2) as i64;
+
+        FloatImage::from_fn(self.horizontal_size(), self.vertical_size(), |x, y| {
+            iproduct!(0..kernel.height, 0..kernel.width)
+                .map(|(row, column)| {
+                    let weight = kernel.data[row * kernel.width + column];
+                    let dx = column as i64 - half_width;
+                    let dy = row as i64 - half_height;
+
+                    weight * self.reflective(x as i64 + dx, y as i64 + dy)
+                })
+                .sum()
+        })
+    }
+
+    /// Convolve the rows and then the columns of the image with the same 1D kernel, which is a
+    /// lot cheaper than a 2D convolution when the kernel is separable
+    pub fn convolve_separable(&self, weights: &[f64]) -> FloatImage {
+        let half = (weights.len() / 2) as i64;
+
+        let horizontal =
+            FloatImage::from_fn(self.horizontal_size(), self.vertical_size(), |x, y| {
+                weights
+                    .iter()
+                    .enumerate()
+                    .map(|(i, w)| w * self.reflective(x as i64 + i as i64 - half, y as i64))
+                    .sum()
+            });
+
+        FloatImage::from_fn(self.horizontal_size(), self.vertical_size(), |x, y| {
+            weights
+                .iter()
+                .enumerate()
+                .map(|(i, w)| w * horizontal.reflective(x as i64, y as i64 + i as i64 - half))
+                .sum()
+        })
+    }
+
+    /// Blur the image with a Gaussian of standard deviation `sigma`
+    pub fn gaussian_blur(&self, sigma: f64) -> FloatImage {
+        self.convolve_separable(&gaussian_weights(sigma))
+    }
 }
 
 /// Convert from the kernel view of index that goes from the center to the edges to the actual
@@ -231,4 +352,47 @@ mod test {
         assert_eq!(kernel[(1, 1)], 1.);
         assert_eq!(kernel[(1, 0)], 2.);
     }
+
+    #[test]
+    fn gaussian_is_normalized_and_symmetric() {
+        let weights = gaussian_weights(1.5);
+        assert_eq!(weights.len(), 11);
+        assert!((weights.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+        assert_eq!(weights[0], weights[10]);
+
+        let kernel = Kernel::gaussian(1.0);
+        assert_eq!(kernel[(-1, 0)], kernel[(0, 1)]);
+        assert!(kernel[(0, 0)] > kernel[(1, 1)]);
+    }
+
+    #[test]
+    fn correlate_reads_kernel_as_written() {
+        // intensity increases to the right
+        let image = FloatImage::from_fn(5, 5, |x, _| x as f64);
+        let kernel = Kernel::from_vec(vec![0., 0., 0., -1., 0., 1., 0., 0., 0.], 3, 3);
+
+        let correlated = image.correlate(&kernel);
+        assert_eq!(correlated.get(2, 2), Some(2.0));
+        // reflected at the border
+        assert_eq!(correlated.get(0, 2), Some(0.0));
+    }
+
+    #[test]
+    fn laplacian_of_gaussian_sums_to_zero() {
+        let kernel = Kernel::laplacian_of_gaussian(1.4);
+        assert_eq!(kernel.width, 11);
+        assert!(kernel.data.iter().sum::<f64>().abs() < 1e-12);
+        // negative in the middle, like the 3 x 3 Laplacian
+        assert!(kernel[(0, 0)] < 0.0);
+    }
+
+    #[test]
+    fn blur_keeps_constant_image() {
+        let image = FloatImage::from_fn(8, 5, |_, _| 0.25);
+        let blurred = image.gaussian_blur(2.0);
+
+        for v in blurred.data() {
+            assert!((v - 0.25).abs() < 1e-12);
+        }
+    }
 }
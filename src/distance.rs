@@ -1,14 +1,24 @@
-#![allow(dead_code)]
-
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Distance {
     Manhattan,
     Chebyshev,
     Euclidean,
 }
 
+impl Distance {
+    /// The metric this variant stands for
+    pub fn metric(&self) -> &'static dyn DistanceMetric {
+        match self {
+            Distance::Manhattan => &Manhattan,
+            Distance::Chebyshev => &Chebyshev,
+            Distance::Euclidean => &Euclidean,
+        }
+    }
+}
+
 /// Distance metric on R^2
 pub trait DistanceMetric {
     fn distance(&self, x1: u32, y1: u32, x2: u32, y2: u32) -> f64;
@@ -0,0 +1,304 @@
+use crate::canvas_image::CanvasImage;
+use itertools::iproduct;
+
+/// A single channel image with `f64` intensities.
+///
+/// `CanvasImage` only has 8 bits per channel, which is not enough for operations that produce
+/// negative values or need to accumulate over many steps, so those work on `FloatImage`s instead
+/// and convert back at the very end. Unless stated otherwise, intensities are expected to be in
+/// [0, 1].
+#[derive(Debug, Clone, PartialEq)]
+pub struct FloatImage {
+    data: Vec<f64>,
+    width: u32,
+    height: u32,
+}
+
+/// Reflect `i` back into [0, n) without repeating the pixel on the border, i.e. -1 maps to 1
+/// and n maps to n - 2.
+pub(crate) fn reflect(i: i64, n: i64) -> i64 {
+    if n == 1 {
+        return 0;
+    }
+
+    let period = 2 * (n - 1);
+    let i = i.rem_euclid(period);
+
+    if i < n {
+        i
+    } else {
+        period - i
+    }
+}
+
+impl FloatImage {
+    /// Create an image where every pixel is 0
+    pub fn new(width: u32, height: u32) -> FloatImage {
+        FloatImage {
+            data: vec![0f64; (width * height) as usize],
+            width,
+            height,
+        }
+    }
+
+    pub fn from_vec_with_size(data: Vec<f64>, width: u32, height: u32) -> FloatImage {
+        assert_eq!(data.len(), (width * height) as usize);
+
+        FloatImage {
+            data,
+            width,
+            height,
+        }
+    }
+
+    /// Build the image by evaluating `f` at every pixel, from left to right, top to bottom
+    pub fn from_fn<F>(width: u32, height: u32, f: F) -> FloatImage
+    where
+        F: Fn(u32, u32) -> f64,
+    {
+        let data = iproduct!(0..height, 0..width)
+            .map(|(y, x)| f(x, y))
+            .collect();
+
+        FloatImage::from_vec_with_size(data, width, height)
+    }
+
+    /// Returns the number of pixels in the horizontal direction
+    pub fn horizontal_size(&self) -> u32 {
+        self.width
+    }
+
+    /// Returns the number of pixels in the vertical direction
+    pub fn vertical_size(&self) -> u32 {
+        self.height
+    }
+
+    pub fn data(&self) -> &[f64] {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut [f64] {
+        &mut self.data
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> Option<f64> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        self.data.get((y * self.width + x) as usize).copied()
+    }
+
+    pub fn set(&mut self, x: u32, y: u32, value: f64) {
+        assert!(x < self.width && y < self.height, "out of bounds");
+        self.data[(y * self.width + x) as usize] = value;
+    }
+
+    /// Read the pixel at (x, y), points outside of the image are reflected back in across the
+    /// border
+    pub fn reflective(&self, x: i64, y: i64) -> f64 {
+        let x = reflect(x, self.width as i64);
+        let y = reflect(y, self.height as i64);
+
+        self.data[(y * self.width as i64 + x) as usize]
+    }
+
+    /// Read the pixel at (x, y), points outside of the image take the value of the closest pixel
+    /// on the border
+    pub fn clamped(&self, x: i64, y: i64) -> f64 {
+        let x = x.clamp(0, self.width as i64 - 1);
+        let y = y.clamp(0, self.height as i64 - 1);
+
+        self.data[(y * self.width as i64 + x) as usize]
+    }
+
+    /// Apply `f` to every pixel
+    pub fn map<F>(&self, f: F) -> FloatImage
+    where
+        F: Fn(f64) -> f64,
+    {
+        let data = self.data.iter().map(|v| f(*v)).collect();
+        FloatImage::from_vec_with_size(data, self.width, self.height)
+    }
+
+    /// Combine two images of the same size pixel by pixel
+    pub fn zip_map<F>(&self, other: &FloatImage, f: F) -> FloatImage
+    where
+        F: Fn(f64, f64) -> f64,
+    {
+        assert_eq!(self.width, other.width);
+        assert_eq!(self.height, other.height);
+
+        let data = Iterator::zip(self.data.iter(), other.data.iter())
+            .map(|(a, b)| f(*a, *b))
+            .collect();
+        FloatImage::from_vec_with_size(data, self.width, self.height)
+    }
+
+    /// Returns the smallest and largest intensity in the image
+    pub fn min_max(&self) -> (f64, f64) {
+        self.data
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| {
+                (min.min(*v), max.max(*v))
+            })
+    }
+
+    /// Linearly rescale the intensities so the smallest one becomes 0 and the largest one 1
+    pub fn normalized(&self) -> FloatImage {
+        let (min, max) = self.min_max();
+        let range = max - min;
+
+        if range <= f64::EPSILON {
+            return FloatImage::new(self.width, self.height);
+        }
+
+        self.map(|v| (v - min) / range)
+    }
+
+    /// Convert to an opaque greyscale `CanvasImage`, intensities are clamped to [0, 1]
+    pub fn to_greyscale(&self) -> CanvasImage {
+        let rgba = self.data.iter().flat_map(|v| {
+            let v = (v * 255.0).round().clamp(0.0, 255.0) as u8;
+            [v, v, v, 255]
+        });
+
+        let buffer = Vec::from_iter(rgba);
+        CanvasImage::from_vec_with_size(buffer, self.width, self.height)
+    }
+}
+
+/// Summed area table of a `FloatImage`, answers the sum over any rectangle in constant time
+#[derive(Debug, Clone, PartialEq)]
+pub struct IntegralImage {
+    /// (width + 1) x (height + 1), the first row and column are all 0
+    sums: Vec<f64>,
+    width: u32,
+    height: u32,
+}
+
+impl IntegralImage {
+    pub fn new(image: &FloatImage) -> IntegralImage {
+        let width = image.width;
+        let height = image.height;
+        let stride = width as usize + 1;
+
+        let mut sums = vec![0f64; stride * (height as usize + 1)];
+        for y in 0..height as usize {
+            let mut row_sum = 0f64;
+            for x in 0..width as usize {
+                row_sum += image.data[y * width as usize + x];
+                sums[(y + 1) * stride + x + 1] = sums[y * stride + x + 1] + row_sum;
+            }
+        }
+
+        IntegralImage {
+            sums,
+            width,
+            height,
+        }
+    }
+
+    /// Clamp the inclusive rectangle to the image, returns `None` if nothing is left
+    fn clamp_rect(
+        &self,
+        x0: i64,
+        y0: i64,
+        x1: i64,
+        y1: i64,
+    ) -> Option<(usize, usize, usize, usize)> {
+        let x0 = x0.max(0);
+        let y0 = y0.max(0);
+        let x1 = x1.min(self.width as i64 - 1);
+        let y1 = y1.min(self.height as i64 - 1);
+
+        if x0 > x1 || y0 > y1 {
+            return None;
+        }
+
+        Some((x0 as usize, y0 as usize, x1 as usize, y1 as usize))
+    }
+
+    /// Sum over the inclusive rectangle [x0, x1] x [y0, y1], the parts of the rectangle outside
+    /// of the image are ignored
+    pub fn sum(&self, x0: i64, y0: i64, x1: i64, y1: i64) -> f64 {
+        match self.clamp_rect(x0, y0, x1, y1) {
+            Some((x0, y0, x1, y1)) => {
+                let stride = self.width as usize + 1;
+                self.sums[(y1 + 1) * stride + x1 + 1]
+                    - self.sums[y0 * stride + x1 + 1]
+                    - self.sums[(y1 + 1) * stride + x0]
+                    + self.sums[y0 * stride + x0]
+            }
+            None => 0.0,
+        }
+    }
+
+    /// Mean over the inclusive rectangle [x0, x1] x [y0, y1], the parts of the rectangle outside
+    /// of the image are ignored
+    pub fn mean(&self, x0: i64, y0: i64, x1: i64, y1: i64) -> f64 {
+        match self.clamp_rect(x0, y0, x1, y1) {
+            Some((cx0, cy0, cx1, cy1)) => {
+                let area = ((cx1 - cx0 + 1) * (cy1 - cy0 + 1)) as f64;
+                self.sum(x0, y0, x1, y1) / area
+            }
+            None => 0.0,
+        }
+    }
+}
+
+impl FloatImage {
+    /// Replace every pixel with the mean of the (2 * radius + 1) square centered on it, the
+    /// window shrinks near the borders instead of padding
+    pub fn box_mean(&self, radius: u32) -> FloatImage {
+        let integral = IntegralImage::new(self);
+        let r = radius as i64;
+
+        FloatImage::from_fn(self.width, self.height, |x, y| {
+            let (x, y) = (x as i64, y as i64);
+            integral.mean(x - r, y - r, x + r, y + r)
+        })
+    }
+}
+
+impl From<FloatImage> for Vec<f64> {
+    fn from(image: FloatImage) -> Self {
+        image.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reflect_does_not_repeat_border() {
+        assert_eq!(reflect(-1, 4), 1);
+        assert_eq!(reflect(-3, 4), 3);
+        assert_eq!(reflect(4, 4), 2);
+        assert_eq!(reflect(6, 4), 0);
+        assert_eq!(reflect(7, 4), 1);
+        assert_eq!(reflect(5, 1), 0);
+    }
+
+    #[test]
+    fn normalized_spans_unit_interval() {
+        let image = FloatImage::from_vec_with_size(vec![-2.0, 0.0, 2.0, 6.0], 2, 2);
+        let normalized = image.normalized();
+
+        assert_eq!(normalized.data(), &[0.0, 0.25, 0.5, 1.0]);
+    }
+
+    #[test]
+    fn integral_image_sums_rectangles() {
+        let image = FloatImage::from_fn(4, 3, |x, y| (y * 4 + x) as f64);
+        let integral = IntegralImage::new(&image);
+
+        assert_eq!(integral.sum(0, 0, 3, 2), 66.0);
+        assert_eq!(integral.sum(1, 1, 2, 2), 5.0 + 6.0 + 9.0 + 10.0);
+        // clipped to the image
+        assert_eq!(integral.sum(-5, -5, 0, 0), 0.0);
+        assert_eq!(integral.mean(2, 1, 10, 1), 6.5);
+        assert_eq!(integral.sum(5, 5, 9, 9), 0.0);
+    }
+}
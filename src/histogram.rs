@@ -12,15 +12,26 @@ pub struct Histogram {
 
 impl Histogram {
     pub(crate) fn from_channel_iterator(channel: &mut ChannelIterator) -> Self {
+        Self::from_values(channel)
+    }
+
+    /// Count how often every intensity shows up in `values`
+    pub fn from_values(values: impl IntoIterator<Item = u8>) -> Self {
         let mut buckets = vec![0f64; 256];
 
-        for intensity in channel {
+        for intensity in values {
             buckets[intensity as usize] += 1f64;
         }
 
         Histogram { buckets }
     }
 
+    /// A histogram with the given counts (or weights) for the 256 intensities
+    pub fn from_buckets(buckets: Vec<f64>) -> Self {
+        assert_eq!(buckets.len(), 256, "a histogram has 256 buckets");
+        Histogram { buckets }
+    }
+
     /// Returns the number of pixels in the image that have the given intensity
     pub fn bucket(&self) -> &[f64] {
         &self.buckets
@@ -62,6 +73,23 @@ impl Histogram {
         hist.cumulative()
     }
 
+    /// Histogram specification: the lookup table that makes values distributed like `self`
+    /// distributed like `target`. Every intensity goes to the smallest target intensity whose
+    /// cumulative frequency reaches its own.
+    pub fn matching_table(&self, target: &Histogram) -> [u8; 256] {
+        let source = self.cumulative_normalized();
+        let target = target.cumulative_normalized();
+
+        let mut table = [0u8; 256];
+        for (entry, frequency) in table.iter_mut().zip(source.bucket()) {
+            // a little slack so rounding in the sums doesn't skip to the next intensity
+            let level = target.buckets.partition_point(|t| *t < frequency - 1e-9);
+            *entry = level.min(255) as u8;
+        }
+
+        table
+    }
+
     pub fn is_normalized(&self) -> bool {
         let sum: f64 = self.buckets.iter().sum();
         approx_eq!(f64, sum, 1.0, epsilon = 0.0001)
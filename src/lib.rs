@@ -8,9 +8,11 @@ pub mod color_space;
 pub mod convolution;
 pub mod distance;
 pub mod flip;
+pub mod float_image;
 pub mod histogram;
 pub mod image_index;
 pub mod interpolation;
+pub mod lut;
 pub mod scaling;
 pub mod single_pixel_transformation;
 pub mod utils;
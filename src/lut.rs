@@ -0,0 +1,501 @@
+use crate::canvas_image::CanvasImage;
+use crate::color_space::Srgb;
+use crate::interpolation::lerp;
+use color_eyre::eyre::{bail, eyre};
+use std::fmt::Write;
+use web_sys::ImageData;
+
+use wasm_bindgen::prelude::*;
+
+/// Where a channel value falls inside `[min, max]`, as a position in [0, 1]
+fn normalize(value: f64, min: f64, max: f64) -> f64 {
+    ((value - min) / (max - min)).clamp(0.0, 1.0)
+}
+
+/// Per channel lookup table, the entries are evenly spaced over the input domain and looked up
+/// with linear interpolation
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lut1D {
+    /// One (red, green, blue) output per input step
+    pub entries: Vec<[f64; 3]>,
+    pub domain_min: [f64; 3],
+    pub domain_max: [f64; 3],
+}
+
+impl Lut1D {
+    /// `size` entries of `f`, which maps every channel alike from [0, 1] to [0, 1]
+    pub fn from_fn(size: usize, f: impl Fn(f64) -> f64) -> Lut1D {
+        assert!(size >= 2, "a LUT needs at least 2 entries");
+        let entries = (0..size)
+            .map(|i| [f(i as f64 / (size - 1) as f64); 3])
+            .collect();
+
+        Lut1D {
+            entries,
+            domain_min: [0.0; 3],
+            domain_max: [1.0; 3],
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Look `value` up in the table of channel `c` (0 for red, 1 for green and 2 for blue)
+    pub fn lookup(&self, c: usize, value: f64) -> f64 {
+        let position = normalize(value, self.domain_min[c], self.domain_max[c]);
+        let position = position * (self.size() - 1) as f64;
+        let i = (position.floor() as usize).min(self.size() - 2);
+
+        lerp(
+            self.entries[i][c],
+            self.entries[i + 1][c],
+            position - i as f64,
+        )
+    }
+
+    pub fn apply(&self, color: Srgb) -> Srgb {
+        Srgb::new(
+            self.lookup(0, color.r),
+            self.lookup(1, color.g),
+            self.lookup(2, color.b),
+        )
+    }
+
+    /// The whole table evaluated for every 8 bit value, per channel
+    fn bytes(&self) -> [[u8; 256]; 3] {
+        let mut bytes = [[0; 256]; 3];
+        for (c, table) in bytes.iter_mut().enumerate() {
+            for (v, entry) in table.iter_mut().enumerate() {
+                let value = self.lookup(c, v as f64 / 255.0);
+                *entry = (value * 255.0).round().clamp(0.0, 255.0) as u8;
+            }
+        }
+
+        bytes
+    }
+}
+
+/// How colors between the points of a 3D LUT are found
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum LutInterpolation {
+    /// Blend the 8 corners of the surrounding cube
+    Trilinear,
+    /// Blend the 4 corners of the tetrahedron within the cube the color falls in, cheaper and
+    /// keeps greys on the grey axis
+    #[default]
+    Tetrahedral,
+}
+
+/// Lookup table over the whole RGB cube, `size` points along every axis
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lut3D {
+    pub size: usize,
+    /// `size`³ outputs with red changing fastest, then green, then blue, as in .cube files
+    pub entries: Vec<[f64; 3]>,
+    pub domain_min: [f64; 3],
+    pub domain_max: [f64; 3],
+    pub interpolation: LutInterpolation,
+}
+
+impl Lut3D {
+    /// `f` sampled on a `size`³ grid
+    pub fn from_fn(size: usize, f: impl Fn(Srgb) -> Srgb) -> Lut3D {
+        assert!(size >= 2, "a LUT needs at least 2 points per axis");
+        let step = |i: usize| i as f64 / (size - 1) as f64;
+        let entries = itertools::iproduct!(0..size, 0..size, 0..size)
+            .map(|(b, g, r)| {
+                let Srgb { r, g, b } = f(Srgb::new(step(r), step(g), step(b)));
+                [r, g, b]
+            })
+            .collect();
+
+        Lut3D {
+            size,
+            entries,
+            domain_min: [0.0; 3],
+            domain_max: [1.0; 3],
+            interpolation: LutInterpolation::default(),
+        }
+    }
+
+    pub fn identity(size: usize) -> Lut3D {
+        Lut3D::from_fn(size, |color| color)
+    }
+
+    fn entry(&self, r: usize, g: usize, b: usize) -> [f64; 3] {
+        self.entries[r + self.size * (g + self.size * b)]
+    }
+
+    /// The cell `color` falls in, as the index of its lower corner and the position inside it
+    fn cell(&self, color: Srgb) -> ([usize; 3], [f64; 3]) {
+        let mut corner = [0; 3];
+        let mut fraction = [0.0; 3];
+        for (c, value) in [color.r, color.g, color.b].into_iter().enumerate() {
+            let position = normalize(value, self.domain_min[c], self.domain_max[c]);
+            let position = position * (self.size - 1) as f64;
+            corner[c] = (position.floor() as usize).min(self.size - 2);
+            fraction[c] = position - corner[c] as f64;
+        }
+
+        (corner, fraction)
+    }
+
+    pub fn trilinear(&self, color: Srgb) -> Srgb {
+        let ([r, g, b], [fr, fg, fb]) = self.cell(color);
+
+        let mut out = [0.0; 3];
+        for (dr, dg, db) in itertools::iproduct!(0..2, 0..2, 0..2) {
+            let weight = [(dr, fr), (dg, fg), (db, fb)]
+                .iter()
+                .map(|(d, f)| if *d == 1 { *f } else { 1.0 - f })
+                .product::<f64>();
+            let corner = self.entry(r + dr, g + dg, b + db);
+            out.iter_mut()
+                .zip(corner)
+                .for_each(|(o, c)| *o += weight * c);
+        }
+
+        Srgb::new(out[0], out[1], out[2])
+    }
+
+    pub fn tetrahedral(&self, color: Srgb) -> Srgb {
+        let ([r, g, b], [fr, fg, fb]) = self.cell(color);
+        let corner = |dr, dg, db| self.entry(r + dr, g + dg, b + db);
+
+        // walk from the lowest to the highest corner along the axes in order of their fraction
+        let (c000, c111) = (corner(0, 0, 0), corner(1, 1, 1));
+        let (weights, c1, c2) = if fr > fg {
+            if fg > fb {
+                (
+                    [1.0 - fr, fr - fg, fg - fb, fb],
+                    corner(1, 0, 0),
+                    corner(1, 1, 0),
+                )
+            } else if fr > fb {
+                (
+                    [1.0 - fr, fr - fb, fb - fg, fg],
+                    corner(1, 0, 0),
+                    corner(1, 0, 1),
+                )
+            } else {
+                (
+                    [1.0 - fb, fb - fr, fr - fg, fg],
+                    corner(0, 0, 1),
+                    corner(1, 0, 1),
+                )
+            }
+        } else if fb > fg {
+            (
+                [1.0 - fb, fb - fg, fg - fr, fr],
+                corner(0, 0, 1),
+                corner(0, 1, 1),
+            )
+        } else if fb > fr {
+            (
+                [1.0 - fg, fg - fb, fb - fr, fr],
+                corner(0, 1, 0),
+                corner(0, 1, 1),
+            )
+        } else {
+            (
+                [1.0 - fg, fg - fr, fr - fb, fb],
+                corner(0, 1, 0),
+                corner(1, 1, 0),
+            )
+        };
+
+        let [w0, w1, w2, w3] = weights;
+        let out: [f64; 3] =
+            std::array::from_fn(|c| w0 * c000[c] + w1 * c1[c] + w2 * c2[c] + w3 * c111[c]);
+        Srgb::new(out[0], out[1], out[2])
+    }
+
+    pub fn apply(&self, color: Srgb) -> Srgb {
+        match self.interpolation {
+            LutInterpolation::Trilinear => self.trilinear(color),
+            LutInterpolation::Tetrahedral => self.tetrahedral(color),
+        }
+    }
+}
+
+/// A color lookup table, as stored in Adobe/Resolve .cube files
+#[derive(Debug, Clone, PartialEq)]
+pub enum Lut {
+    OneD(Lut1D),
+    ThreeD(Lut3D),
+}
+
+impl From<Lut1D> for Lut {
+    fn from(lut: Lut1D) -> Self {
+        Lut::OneD(lut)
+    }
+}
+
+impl From<Lut3D> for Lut {
+    fn from(lut: Lut3D) -> Self {
+        Lut::ThreeD(lut)
+    }
+}
+
+impl Lut {
+    pub fn apply(&self, color: Srgb) -> Srgb {
+        match self {
+            Lut::OneD(lut) => lut.apply(color),
+            Lut::ThreeD(lut) => lut.apply(color),
+        }
+    }
+
+    /// Parse the text of a .cube file. Titles and keywords other than the sizes and the domain
+    /// are skipped, 3D LUTs get the default interpolation.
+    pub fn from_cube(text: &str) -> color_eyre::Result<Lut> {
+        let mut size_1d = None;
+        let mut size_3d = None;
+        let mut domain_min = [0.0; 3];
+        let mut domain_max = [1.0; 3];
+        let mut entries = Vec::new();
+
+        let numbers = |words: &[&str], line: usize| -> color_eyre::Result<Vec<f64>> {
+            words
+                .iter()
+                .map(|w| {
+                    w.parse()
+                        .map_err(|_| eyre!("line {line}: {w} is not a number"))
+                })
+                .collect()
+        };
+        let triple = |words: &[&str], line: usize| -> color_eyre::Result<[f64; 3]> {
+            match numbers(words, line)?[..] {
+                [r, g, b] => Ok([r, g, b]),
+                _ => bail!("line {line}: expected 3 numbers"),
+            }
+        };
+
+        for (i, line) in text.lines().enumerate() {
+            let line_number = i + 1;
+            let words: Vec<_> = line.split_whitespace().collect();
+            let Some(keyword) = words.first() else {
+                continue;
+            };
+
+            match *keyword {
+                k if k.starts_with('#') => {}
+                "LUT_1D_SIZE" | "LUT_3D_SIZE" => {
+                    let size = match words[1..] {
+                        [size] => size
+                            .parse::<usize>()
+                            .map_err(|_| eyre!("line {line_number}: bad size {size}"))?,
+                        _ => bail!("line {line_number}: expected a single size"),
+                    };
+                    if *keyword == "LUT_1D_SIZE" {
+                        size_1d = Some(size);
+                    } else {
+                        size_3d = Some(size);
+                    }
+                }
+                "DOMAIN_MIN" => domain_min = triple(&words[1..], line_number)?,
+                "DOMAIN_MAX" => domain_max = triple(&words[1..], line_number)?,
+                "LUT_1D_INPUT_RANGE" | "LUT_3D_INPUT_RANGE" => {
+                    match numbers(&words[1..], line_number)?[..] {
+                        [min, max] => {
+                            domain_min = [min; 3];
+                            domain_max = [max; 3];
+                        }
+                        _ => bail!("line {line_number}: expected the minimum and the maximum"),
+                    }
+                }
+                k if k.starts_with(|c: char| c.is_ascii_alphabetic()) => {}
+                _ => entries.push(triple(&words, line_number)?),
+            }
+        }
+
+        if (0..3).any(|c| domain_min[c] >= domain_max[c]) {
+            bail!("the domain minimum has to be below the maximum");
+        }
+
+        let lut: Lut = match (size_1d, size_3d) {
+            (Some(size), None) => {
+                if !(2..=65536).contains(&size) {
+                    bail!("LUT_1D_SIZE has to be in [2, 65536]");
+                }
+                if entries.len() != size {
+                    bail!("expected {size} entries, found {}", entries.len());
+                }
+                Lut1D {
+                    entries,
+                    domain_min,
+                    domain_max,
+                }
+                .into()
+            }
+            (None, Some(size)) => {
+                if !(2..=256).contains(&size) {
+                    bail!("LUT_3D_SIZE has to be in [2, 256]");
+                }
+                if entries.len() != size.pow(3) {
+                    bail!("expected {} entries, found {}", size.pow(3), entries.len());
+                }
+                Lut3D {
+                    size,
+                    entries,
+                    domain_min,
+                    domain_max,
+                    interpolation: LutInterpolation::default(),
+                }
+                .into()
+            }
+            (None, None) => bail!("missing LUT_1D_SIZE or LUT_3D_SIZE"),
+            (Some(_), Some(_)) => bail!("a .cube file holds either a 1D or a 3D LUT, not both"),
+        };
+
+        Ok(lut)
+    }
+
+    /// The LUT as the text of a .cube file
+    pub fn to_cube(&self) -> String {
+        let (size_line, entries, domain_min, domain_max) = match self {
+            Lut::OneD(lut) => (
+                format!("LUT_1D_SIZE {}", lut.size()),
+                &lut.entries,
+                lut.domain_min,
+                lut.domain_max,
+            ),
+            Lut::ThreeD(lut) => (
+                format!("LUT_3D_SIZE {}", lut.size),
+                &lut.entries,
+                lut.domain_min,
+                lut.domain_max,
+            ),
+        };
+
+        let mut cube = size_line + "\n";
+        if domain_min != [0.0; 3] || domain_max != [1.0; 3] {
+            let [r, g, b] = domain_min;
+            writeln!(cube, "DOMAIN_MIN {r} {g} {b}").unwrap();
+            let [r, g, b] = domain_max;
+            writeln!(cube, "DOMAIN_MAX {r} {g} {b}").unwrap();
+        }
+        cube.push('\n');
+        for [r, g, b] in entries {
+            writeln!(cube, "{r:.6} {g:.6} {b:.6}").unwrap();
+        }
+
+        cube
+    }
+}
+
+impl CanvasImage {
+    /// Run every pixel through `lut`, alpha is left untouched. A 1D LUT is evaluated once for
+    /// all 256 values of each channel, a 3D LUT is interpolated for every pixel.
+    pub fn apply_lut(&self, lut: &Lut) -> CanvasImage {
+        match lut {
+            Lut::OneD(lut) => {
+                let [red, green, blue] = lut.bytes();
+                let rgba = self
+                    .rgba_iter()
+                    .flat_map(|(r, g, b, a)| {
+                        [red[r as usize], green[g as usize], blue[b as usize], a]
+                    })
+                    .collect();
+
+                CanvasImage::from_vec_with_size(rgba, self.horizontal_size(), self.vertical_size())
+            }
+            Lut::ThreeD(lut) => self.map_colors(|color| lut.apply(color)),
+        }
+    }
+}
+
+/// Apply the LUT in the text of a .cube file
+#[wasm_bindgen]
+pub fn apply_cube_lut(
+    image: ImageData,
+    cube: &str,
+    interpolation: LutInterpolation,
+) -> Result<ImageData, String> {
+    let image = CanvasImage::from_image_data(image);
+    let mut lut = Lut::from_cube(cube).map_err(|e| e.to_string())?;
+    if let Lut::ThreeD(lut) = &mut lut {
+        lut.interpolation = interpolation;
+    }
+
+    Ok(image.apply_lut(&lut).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INVERT_CUBE: &str = "TITLE \"invert\"
+# two points per axis
+LUT_3D_SIZE 2
+
+1.0 1.0 1.0
+0.0 1.0 1.0
+1.0 0.0 1.0
+0.0 0.0 1.0
+1.0 1.0 0.0
+0.0 1.0 0.0
+1.0 0.0 0.0
+0.0 0.0 0.0
+";
+
+    #[test]
+    fn cube_files() {
+        let lut = Lut::from_cube(INVERT_CUBE).unwrap();
+        let Lut::ThreeD(cube) = &lut else {
+            panic!("expected a 3D LUT");
+        };
+        assert_eq!(cube.size, 2);
+        assert_eq!(cube.entry(1, 0, 0), [0.0, 1.0, 1.0]);
+
+        let color = Srgb::new(0.2, 0.5, 0.9);
+        for interpolation in [LutInterpolation::Trilinear, LutInterpolation::Tetrahedral] {
+            let cube = Lut3D {
+                interpolation,
+                ..cube.clone()
+            };
+            let Srgb { r, g, b } = cube.apply(color);
+            assert!((r - 0.8).abs() < 1e-12 && (g - 0.5).abs() < 1e-12 && (b - 0.1).abs() < 1e-12);
+        }
+
+        // writing and reading back gives the same LUT
+        assert_eq!(Lut::from_cube(&lut.to_cube()).unwrap(), lut);
+        let gamma: Lut = Lut1D {
+            domain_max: [2.0; 3],
+            ..Lut1D::from_fn(5, |v| v * v)
+        }
+        .into();
+        assert_eq!(Lut::from_cube(&gamma.to_cube()).unwrap(), gamma);
+
+        assert!(Lut::from_cube("LUT_3D_SIZE 2\n0 0 0\n").is_err());
+        assert!(Lut::from_cube("LUT_1D_SIZE 2\n0 0 0\n1 x 1\n").is_err());
+        assert!(Lut::from_cube("0 0 0\n1 1 1\n").is_err());
+    }
+
+    #[test]
+    fn interpolation_between_points() {
+        // a coarse grid reproduces anything linear exactly, whichever way it interpolates
+        let linear = |c: Srgb| Srgb::new(0.5 * c.r + 0.25 * c.b, c.g, 1.0 - c.b);
+        let mut lut = Lut3D::from_fn(3, linear);
+
+        for (r, g, b) in itertools::iproduct!(0..8, 0..8, 0..8) {
+            let color = Srgb::new(r as f64 / 7.0, g as f64 / 7.0, b as f64 / 7.0);
+            let expected = linear(color);
+            for interpolation in [LutInterpolation::Trilinear, LutInterpolation::Tetrahedral] {
+                lut.interpolation = interpolation;
+                let actual = lut.apply(color);
+                assert!((actual.r - expected.r).abs() < 1e-12);
+                assert!((actual.b - expected.b).abs() < 1e-12);
+            }
+        }
+
+        let image = CanvasImage::from_vec_with_size(vec![0, 64, 255, 9, 128, 128, 128, 255], 2, 1);
+        let identity = image.apply_lut(&Lut3D::identity(17).into());
+        assert_eq!(identity.rgba(0, 0), Some((0, 64, 255, 9)));
+
+        let squared = image.apply_lut(&Lut1D::from_fn(256, |v| v * v).into());
+        assert_eq!(squared.rgba(0, 0), Some((0, 16, 255, 9)));
+        assert_eq!(squared.rgba(1, 0), Some((64, 64, 64, 255)));
+    }
+}
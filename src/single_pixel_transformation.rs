@@ -1,46 +1,55 @@
 use crate::canvas_image::CanvasImage;
-use wasm_bindgen::Clamped;
+use crate::lut::Lut1D;
 
 use wasm_bindgen::prelude::*;
 use web_sys::ImageData;
 
+/// Table of `f` for every 8 bit value, with the result truncated to 8 bits. There is one entry
+/// per 8 bit value and every entry is a whole 8 bit value, so looking them up gives back exactly
+/// the truncated values.
+fn truncating_lut(f: impl Fn(f64) -> f64) -> Lut1D {
+    Lut1D::from_fn(256, |v| {
+        let value = (v * 255f64).round();
+        f(value).clamp(0f64, 255f64).floor() / 255f64
+    })
+}
+
+fn linear_lut(gain: f64, bias: f64) -> Lut1D {
+    truncating_lut(|v| v * gain + bias)
+}
+
+fn gamma_lut(gamma: f64) -> Lut1D {
+    truncating_lut(|v| 255f64 * (v / 255f64).powf(gamma))
+}
+
 #[wasm_bindgen]
 pub fn linear_transformation(image: ImageData, gain: f64, bias: f64) -> ImageData {
     let image = CanvasImage::from_image_data(image);
-
-    let transformed = image.rgba_iter().flat_map(|(r, g, b, a)| {
-        let r = (r as f64 * gain + bias).clamp(0f64, 255f64) as u8;
-        let g = (g as f64 * gain + bias).clamp(0f64, 255f64) as u8;
-        let b = (b as f64 * gain + bias).clamp(0f64, 255f64) as u8;
-
-        [r, g, b, a]
-    });
-    let buffer = Vec::from_iter(transformed);
-    ImageData::new_with_u8_clamped_array_and_sh(
-        Clamped(&buffer),
-        image.horizontal_size(),
-        image.vertical_size(),
-    )
-    .unwrap()
+    image.apply_lut(&linear_lut(gain, bias).into()).into()
 }
 
 #[wasm_bindgen]
 pub fn gamma_transformation(image: ImageData, gamma: f64) -> ImageData {
     let image = CanvasImage::from_image_data(image);
+    image.apply_lut(&gamma_lut(gamma).into()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_are_truncated() {
+        let image = CanvasImage::from_vec_with_size(vec![3, 100, 255, 7, 0, 200, 171, 255], 2, 1);
+
+        let linear = image.apply_lut(&linear_lut(1.5, 0.0).into());
+        assert_eq!(linear.rgba_slice(), &[4, 150, 255, 7, 0, 255, 255, 255]);
+
+        let gamma = image.apply_lut(&gamma_lut(0.5).into());
+        assert_eq!(gamma.rgba(0, 0), Some((27, 159, 255, 7)));
 
-    let rgba = image.rgba_iter().flat_map(|(r, g, b, a)| {
-        let r = (255f64 * (r as f64 / 255f64).powf(gamma)) as u8;
-        let g = (255f64 * (g as f64 / 255f64).powf(gamma)) as u8;
-        let b = (255f64 * (b as f64 / 255f64).powf(gamma)) as u8;
-
-        [r, g, b, a]
-    });
-    let buffer = Vec::from_iter(rgba);
-
-    ImageData::new_with_u8_clamped_array_and_sh(
-        Clamped(&buffer),
-        image.horizontal_size(),
-        image.vertical_size(),
-    )
-    .unwrap()
+        // the identity doesn't lose anything to float errors
+        let identity = image.apply_lut(&linear_lut(1.0, 0.0).into());
+        assert_eq!(identity.rgba_slice(), image.rgba_slice());
+    }
 }